@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pha_engine::{
+    io::{bits::BitReadable, bytes::Readable},
+    net::io::InputMemoryStream,
+};
+
+fn read_all<T>(data: &[u8], bits: usize)
+where
+    T: for<'a, 'b> Readable<InputMemoryStream<'a, 'b, ()>>
+        + for<'a, 'b> BitReadable<InputMemoryStream<'a, 'b, ()>>,
+{
+    let _ = T::read_byte(&mut InputMemoryStream::new(data, &mut ()));
+    let _ = T::read_bits(&mut InputMemoryStream::new(data, &mut ()), bits);
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&bits, data)) = data.split_first() else {
+        return;
+    };
+    let bits = bits as usize % 64 + 1;
+
+    read_all::<String>(data, 8);
+    read_all::<Vec<u8>>(data, bits.min(8));
+    read_all::<Vec<u64>>(data, bits);
+    read_all::<Vec<Option<String>>>(data, 8);
+    read_all::<Vec<Vec<i32>>>(data, bits.min(32));
+
+    let _ = Vec::<[glam::Mat4; 2]>::read_byte(&mut InputMemoryStream::new(data, &mut ()));
+    let _ = Vec::<String>::read_byte(&mut InputMemoryStream::new(data, &mut ()));
+});
@@ -0,0 +1,47 @@
+#![no_main]
+
+use std::sync::Arc;
+
+use libfuzzer_sys::fuzz_target;
+use pha_engine::{
+    GameObject, Replicated,
+    linking_context::{LinkingContext, read_game_object, read_game_object_packed},
+    net::io::InputMemoryStream,
+    reflect::Reflect,
+};
+use pha_engine_fuzz::{Everything, Packed};
+
+fn read(data: &[u8], go: Arc<dyn GameObject>, packed: bool) {
+    let mut ctx = LinkingContext::default();
+    let mut input = InputMemoryStream::new(data, &mut ctx);
+    let mask = go.type_info().all_fields_mask();
+
+    let _ = if packed {
+        read_game_object_packed(&mut input, &go, mask)
+    } else {
+        read_game_object(&mut input, &go, mask)
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    read(
+        data,
+        Arc::new(Replicated::new(Everything::create_instance())),
+        false,
+    );
+    read(
+        data,
+        Arc::new(Replicated::new(Everything::create_instance())),
+        true,
+    );
+    read(
+        data,
+        Arc::new(Replicated::new(Packed::create_instance())),
+        false,
+    );
+    read(
+        data,
+        Arc::new(Replicated::new(Packed::create_instance())),
+        true,
+    );
+});
@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pha_engine::{
+    linking_context::LinkingContext, net::io::InputMemoryStream, net::network::ReplicationManager,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let registry = pha_engine_fuzz::registry();
+
+    // A replication data packet, minus the packet type.
+    let mut ctx = LinkingContext::default();
+    let mut manager = ReplicationManager::new();
+    let mut input = InputMemoryStream::new(data, &mut ctx);
+    let _ = manager.recv_replicated_objects(&mut input, &registry);
+
+    // A sequence of create, update and destroy actions.
+    let mut ctx = LinkingContext::default();
+    let mut manager = ReplicationManager::new();
+    let mut input = InputMemoryStream::new(data, &mut ctx);
+    while input.remaining_bit_count() > 0 {
+        if manager
+            .process_replication_action(&mut input, &registry)
+            .is_err()
+        {
+            break;
+        }
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pha_engine::{
+    io::bytes::Readable,
+    linking_context::LinkingContext,
+    net::{io::InputMemoryStream, network::ReplicationHeader, packet::PacketHeader},
+};
+
+fuzz_target!(|data: &[u8]| {
+    let mut ctx = LinkingContext::default();
+    let _ = PacketHeader::read_byte(&mut InputMemoryStream::new(data, &mut ctx));
+    let _ = ReplicationHeader::read_byte(&mut InputMemoryStream::new(data, &mut ctx));
+});
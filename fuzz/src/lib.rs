@@ -0,0 +1,63 @@
+//! Types shared by the fuzz targets. They cover every kind of field the reflective reader knows
+//! about, so that arbitrary input reaches all of its branches.
+//!
+//! Run a target with `cargo fuzz run <target>` from the repository root.
+
+use std::sync::Weak;
+
+use pha_engine::{GameObject, net::network::ObjectRegistry, reflect::Reflect};
+
+#[derive(Debug, Default, Reflect)]
+pub struct Item {
+    pub id: u32,
+    pub label: String,
+    pub owner: Option<Weak<dyn GameObject>>,
+}
+
+#[derive(Debug, Default, Reflect)]
+#[reflect(type_id = 1)]
+pub struct Everything {
+    pub alive: bool,
+    pub layer: u8,
+    pub flags: u16,
+    pub score: u64,
+    pub offset: i8,
+    pub delta: i16,
+    pub team: i32,
+    pub ticks: i64,
+    pub speed: f32,
+    pub mass: f64,
+    pub name: String,
+    pub position: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub transform: glam::Mat4,
+    pub target: Option<Weak<dyn GameObject>>,
+    pub items: Vec<Item>,
+    pub slots: [Option<u16>; 3],
+    pub history: Vec<Vec<glam::Vec2>>,
+}
+
+#[derive(Debug, Default, Reflect)]
+#[reflect(type_id = 2)]
+pub struct Packed {
+    #[reflect(bits = 7)]
+    pub health: u32,
+    #[reflect(bits = 5)]
+    pub offset: i8,
+    #[reflect(fixed(min = -100.0, max = 100.0, precision = 0.01))]
+    pub position: glam::Vec3,
+    #[reflect(fixed(min = 0.0, max = 1.0, precision = 0.001))]
+    pub speed: f32,
+    #[reflect(bits = 4)]
+    pub slots: Vec<u8>,
+    pub name: String,
+    pub target: Option<Weak<dyn GameObject>>,
+    pub rest: Everything,
+}
+
+pub fn registry() -> ObjectRegistry {
+    let mut registry = ObjectRegistry::default();
+    registry.register::<Everything>().register::<Packed>();
+
+    registry
+}
@@ -0,0 +1,197 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Attribute, Data, DeriveInput, Error, Expr, Fields, LitInt, ext::IdentExt, parse_macro_input,
+};
+
+/// Derives `pha_engine::reflect::Reflect` for a struct with named fields.
+///
+/// Field types are mapped to `Ty` through `pha_engine::reflect::ReflectTy`, so an unsupported
+/// type is a compile error. The struct itself also gets a `ReflectTy` impl, so it can be nested
+/// in other reflected structs. The type id defaults to the FNV-1a hash of the struct name.
+///
+/// Attributes:
+/// - `#[reflect(type_id = 42)]` on the struct overrides the type id.
+/// - `#[reflect(skip)]` on a field excludes it from replication.
+/// - `#[reflect(default = expr)]` on a field sets its value in `create_instance`.
+/// - `#[reflect(bits = 7)]` on an integer field packs it into 7 bits in bit streams.
+/// - `#[reflect(fixed(min = -1.0, max = 1.0, precision = 0.01))]` on a float field packs it as
+///   fixed-point in bit streams.
+#[proc_macro_derive(Reflect, attributes(reflect))]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    skip: bool,
+    default: Option<Expr>,
+    packing: Option<TokenStream2>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "`Reflect` cannot be derived for generic types",
+        ));
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "`Reflect` can only be derived for structs",
+        ));
+    };
+
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &data.fields,
+            "`Reflect` can only be derived for structs with named fields",
+        ));
+    };
+
+    let ident = &input.ident;
+    let type_id = match parse_type_id(&input.attrs)? {
+        Some(type_id) => type_id,
+        None => fnv1a(&ident.unraw().to_string()),
+    };
+
+    let mut members = vec![];
+    let mut inits = vec![];
+
+    for field in &fields.named {
+        let attrs = parse_field_attrs(&field.attrs)?;
+        let name = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+
+        inits.push(match attrs.default {
+            Some(expr) => quote!(#name: #expr),
+            None => quote!(#name: ::core::default::Default::default()),
+        });
+
+        if !attrs.skip {
+            let name_str = name.unraw().to_string();
+            let packing = attrs.packing;
+            // SAFETY: the offset is taken from the very field the accessor returns.
+            members.push(quote! {
+                unsafe {
+                    ::pha_engine::reflect::MemberField::new(
+                        #name_str,
+                        <#ty as ::pha_engine::reflect::ReflectTy>::TY,
+                        ::core::mem::offset_of!(#ident, #name),
+                        |owner: &#ident| &owner.#name,
+                    )
+                }
+                #packing
+            });
+        }
+    }
+
+    Ok(quote! {
+        const _: () = {
+            const INFO: &::pha_engine::reflect::UserDefinedType =
+                &::pha_engine::reflect::UserDefinedType::new::<#ident>(&[#(#members),*]);
+
+            impl ::pha_engine::reflect::Reflect for #ident {
+                fn reflect(&self) -> &'static ::pha_engine::reflect::UserDefinedType {
+                    INFO
+                }
+
+                fn type_id() -> u32 {
+                    #type_id
+                }
+
+                fn create_instance() -> Self {
+                    Self {
+                        #(#inits),*
+                    }
+                }
+            }
+
+            // SAFETY: the struct info is built from this very struct.
+            unsafe impl ::pha_engine::reflect::ReflectTy for #ident {
+                const TY: ::pha_engine::reflect::Ty = ::pha_engine::reflect::Ty::Struct(
+                    &::pha_engine::reflect::StructTy::of::<#ident>(INFO),
+                );
+            }
+        };
+    })
+}
+
+fn parse_type_id(attrs: &[Attribute]) -> syn::Result<Option<u32>> {
+    let mut type_id = None;
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("reflect")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("type_id") {
+                let lit: LitInt = meta.value()?.parse()?;
+                type_id = Some(lit.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported `reflect` attribute, expected `type_id`"))
+            }
+        })?;
+    }
+
+    Ok(type_id)
+}
+
+fn parse_field_attrs(attrs: &[Attribute]) -> syn::Result<FieldAttrs> {
+    let mut field_attrs = FieldAttrs::default();
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("reflect")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                field_attrs.skip = true;
+                Ok(())
+            } else if meta.path.is_ident("default") {
+                field_attrs.default = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("bits") {
+                let bits: LitInt = meta.value()?.parse()?;
+                let bits: u32 = bits.base10_parse()?;
+                field_attrs.packing = Some(quote!(.with_bits(#bits)));
+                Ok(())
+            } else if meta.path.is_ident("fixed") {
+                let (mut min, mut max, mut precision) = (None, None, None);
+                meta.parse_nested_meta(|meta| {
+                    let slot = if meta.path.is_ident("min") {
+                        &mut min
+                    } else if meta.path.is_ident("max") {
+                        &mut max
+                    } else if meta.path.is_ident("precision") {
+                        &mut precision
+                    } else {
+                        return Err(meta.error("expected `min`, `max` or `precision`"));
+                    };
+                    *slot = Some(meta.value()?.parse::<Expr>()?);
+                    Ok(())
+                })?;
+
+                let (Some(min), Some(max), Some(precision)) = (min, max, precision) else {
+                    return Err(meta.error("`fixed` requires `min`, `max` and `precision`"));
+                };
+                field_attrs.packing = Some(quote!(.with_fixed(#min, #max, #precision)));
+                Ok(())
+            } else {
+                Err(meta.error(
+                    "unsupported `reflect` attribute, expected `skip`, `default`, `bits` or `fixed`",
+                ))
+            }
+        })?;
+    }
+
+    Ok(field_attrs)
+}
+
+fn fnv1a(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
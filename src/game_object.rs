@@ -0,0 +1,171 @@
+use std::{
+    any::type_name,
+    fmt::Debug,
+    sync::{
+        PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use crate::{
+    net::io::GameIoError,
+    reflect::{Reflect, UserDefinedType},
+};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// An object whose reflected fields are replicated. [`Replicated`] implements it for any
+/// [`Reflect`] type.
+///
+/// # Safety
+///
+/// [`GameObject::type_info`] must always return the same type info. [`GameObject::with_data`]
+/// and [`GameObject::with_data_mut`] must call `f` with a pointer to a live value of the type it
+/// describes, see [`UserDefinedType::rust_type_id`], which stays valid for the whole call. The
+/// pointer passed by [`GameObject::with_data_mut`] must not be aliased during the call.
+pub unsafe trait GameObject: Sync + Send + Debug {
+    fn id(&self) -> usize;
+    fn class_id(&self) -> u32;
+    fn type_info(&self) -> &'static UserDefinedType;
+
+    /// Bitmask of the [`UserDefinedType`] fields modified since the last
+    /// [`GameObject::clear_dirty_state`].
+    fn dirty_state(&self) -> u64;
+    fn clear_dirty_state(&self);
+
+    /// Calls `f` with a pointer to the data described by [`GameObject::type_info`].
+    fn with_data(
+        &self,
+        f: &mut dyn FnMut(*const u8) -> Result<(), GameIoError>,
+    ) -> Result<(), GameIoError>;
+
+    /// Calls `f` with a mutable pointer to the data described by [`GameObject::type_info`].
+    fn with_data_mut(
+        &self,
+        f: &mut dyn FnMut(*mut u8) -> Result<(), GameIoError>,
+    ) -> Result<(), GameIoError>;
+}
+
+#[derive(Debug)]
+pub struct Replicated<T> {
+    id: usize,
+    dirty_state: AtomicU64,
+    /// Taken up front, so that it is checked once and [`Replicated::mark_dirty`] does not need
+    /// the lock its caller may be holding.
+    type_info: &'static UserDefinedType,
+    data: RwLock<T>,
+}
+
+impl<T: Reflect + 'static> Replicated<T> {
+    /// # Panics
+    ///
+    /// If the type info of `data` does not describe `T`.
+    pub fn new(data: T) -> Self {
+        let type_info = data.reflect();
+        if let Err(err) = type_info.validate_for::<T>() {
+            panic!("cannot replicate {}: {err:?}", type_name::<T>());
+        }
+
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            dirty_state: AtomicU64::new(0),
+            type_info,
+            data: RwLock::new(data),
+        }
+    }
+
+    /// Marks the field at `field` in [`GameObject::type_info`] as modified.
+    ///
+    /// # Panics
+    ///
+    /// If `T` has no such field.
+    pub fn mark_dirty(&self, field: usize) {
+        let field_count = self.type_info.fields.len();
+        assert!(
+            field < field_count,
+            "field {field} out of range for a type with {field_count} fields"
+        );
+
+        self.dirty_state.fetch_or(1 << field, Ordering::Relaxed);
+    }
+}
+
+impl<T> Replicated<T> {
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.data.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.data.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// SAFETY: the type info was checked to describe `T` on construction, and both callbacks get a
+// pointer to the `T` behind the lock, held for the whole call.
+unsafe impl<T> GameObject for Replicated<T>
+where
+    T: Reflect + Sync + Send + Debug + 'static,
+{
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn class_id(&self) -> u32 {
+        T::type_id()
+    }
+
+    fn type_info(&self) -> &'static UserDefinedType {
+        self.type_info
+    }
+
+    fn dirty_state(&self) -> u64 {
+        self.dirty_state.load(Ordering::Relaxed)
+    }
+
+    fn clear_dirty_state(&self) {
+        self.dirty_state.store(0, Ordering::Relaxed);
+    }
+
+    fn with_data(
+        &self,
+        f: &mut dyn FnMut(*const u8) -> Result<(), GameIoError>,
+    ) -> Result<(), GameIoError> {
+        let data = self.read();
+        f(&*data as *const T as *const u8)
+    }
+
+    fn with_data_mut(
+        &self,
+        f: &mut dyn FnMut(*mut u8) -> Result<(), GameIoError>,
+    ) -> Result<(), GameIoError> {
+        let mut data = self.write();
+        f(&mut *data as *mut T as *mut u8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::reflect::Reflect;
+
+    use super::{GameObject, Replicated};
+
+    #[derive(Debug, Default, Reflect)]
+    struct Cat {
+        health: u32,
+        name: String,
+    }
+
+    #[test]
+    fn mark_dirty_sets_the_field_bit() {
+        let cat = Replicated::new(Cat::default());
+        cat.mark_dirty(1);
+
+        assert_eq!(cat.dirty_state(), 0b10);
+    }
+
+    #[test]
+    #[should_panic(expected = "field 2 out of range")]
+    fn mark_dirty_rejects_fields_past_the_type_info() {
+        Replicated::new(Cat::default()).mark_dirty(2);
+    }
+}
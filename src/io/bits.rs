@@ -1,10 +1,20 @@
 use std::string::FromUtf8Error;
 
+use super::{
+    ByteOrder, Reservation, SizeOverflow, VarIntOverflow,
+    bytes::{zigzag_decode, zigzag_encode},
+    preallocation,
+};
+
 pub trait ErasedWriteBitStream {
     type Error;
 
     fn write_byte_bits(&mut self, v: u8, bits: usize) -> Result<(), Self::Error>;
     fn write_any_bits(&mut self, v: &[u8], bits: usize) -> Result<(), Self::Error>;
+
+    fn byte_order(&self) -> ByteOrder {
+        ByteOrder::Little
+    }
 }
 
 pub trait WriteBitStream: ErasedWriteBitStream {
@@ -20,18 +30,34 @@ pub trait WriteBitStream: ErasedWriteBitStream {
     fn write_i32_bits(&mut self, v: i32, bits: usize) -> Result<(), Self::Error>;
     fn write_i64_bits(&mut self, v: i64, bits: usize) -> Result<(), Self::Error>;
 
+    /// Encoded like a `u64`, so the same `bits` work across pointer widths.
     fn write_usize_bits(&mut self, v: usize, bits: usize) -> Result<(), Self::Error>;
+    /// Encoded like an `i64`, so the same `bits` work across pointer widths.
     fn write_isize_bits(&mut self, v: isize, bits: usize) -> Result<(), Self::Error>;
 
     fn write_f32_bits(&mut self, v: f32, bits: usize) -> Result<(), Self::Error>;
     fn write_f64_bits(&mut self, v: f64, bits: usize) -> Result<(), Self::Error>;
+
+    /// Same encoding as [`WriteStream::write_var_u64`](super::bytes::WriteStream::write_var_u64).
+    fn write_var_u64_bits(&mut self, v: u64) -> Result<(), Self::Error>;
+    fn write_var_i64_bits(&mut self, v: i64) -> Result<(), Self::Error>;
+    fn write_var_usize_bits(&mut self, v: usize) -> Result<(), Self::Error>;
 }
 
 pub trait ErasedReadBitStream {
-    type Error: From<FromUtf8Error>;
+    type Error: From<FromUtf8Error> + From<VarIntOverflow> + From<SizeOverflow>;
 
     fn read_byte_bit(&mut self, bits: usize) -> Result<u8, Self::Error>;
     fn read_any_bits(&mut self, v: &mut [u8], bits: usize) -> Result<(), Self::Error>;
+
+    fn byte_order(&self) -> ByteOrder {
+        ByteOrder::Little
+    }
+
+    /// Called with a length read from the wire before room for it is allocated.
+    fn reserve(&mut self, _reservation: Reservation) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
 
 pub trait ReadBitStream: ErasedReadBitStream {
@@ -52,6 +78,10 @@ pub trait ReadBitStream: ErasedReadBitStream {
 
     fn read_f32_bits(&mut self, bits: usize) -> Result<f32, Self::Error>;
     fn read_f64_bits(&mut self, bits: usize) -> Result<f64, Self::Error>;
+
+    fn read_var_u64_bits(&mut self) -> Result<u64, Self::Error>;
+    fn read_var_i64_bits(&mut self) -> Result<i64, Self::Error>;
+    fn read_var_usize_bits(&mut self) -> Result<usize, Self::Error>;
 }
 
 pub trait BitReadable<R: ReadBitStream>: Sized {
@@ -181,7 +211,7 @@ impl<W: WriteBitStream, T: BitWritable<W>> BitWritable<W> for Option<T> {
 
 impl<W: WriteBitStream, T: BitWritable<W>> BitWritable<W> for &[T] {
     fn write_bits(&self, stream: &mut W, bits: usize) -> Result<(), W::Error> {
-        stream.write_usize_bits(self.len(), size_of::<usize>() * 8)?;
+        stream.write_var_usize_bits(self.len())?;
 
         for el in self.iter() {
             el.write_bits(stream, bits)?;
@@ -193,7 +223,7 @@ impl<W: WriteBitStream, T: BitWritable<W>> BitWritable<W> for &[T] {
 
 impl<W: WriteBitStream, T: BitWritable<W>> BitWritable<W> for Vec<T> {
     fn write_bits(&self, stream: &mut W, bits: usize) -> Result<(), W::Error> {
-        stream.write_usize_bits(self.len(), size_of::<usize>() * 8)?;
+        stream.write_var_usize_bits(self.len())?;
 
         for el in self {
             el.write_bits(stream, bits)?;
@@ -308,8 +338,10 @@ impl<R: ReadBitStream, T: BitReadable<R>> BitReadable<R> for Option<T> {
 
 impl<R: ReadBitStream, T: BitReadable<R>> BitReadable<R> for Vec<T> {
     fn read_bits(stream: &mut R, bits: usize) -> Result<Self, R::Error> {
-        let len = stream.read_usize_bits(size_of::<usize>() * 8)?;
-        let mut vec = Vec::with_capacity(len);
+        let len = stream.read_var_usize_bits()?;
+        stream.reserve(Reservation::collection::<T>(len, bits.min(1)))?;
+
+        let mut vec = Vec::with_capacity(preallocation::<T>(len));
         for _ in 0..len {
             vec.push(T::read_bits(stream, bits)?);
         }
@@ -319,14 +351,24 @@ impl<R: ReadBitStream, T: BitReadable<R>> BitReadable<R> for Vec<T> {
 
 impl<R: ReadBitStream> BitReadable<R> for String {
     fn read_bits(stream: &mut R, bits: usize) -> Result<Self, R::Error> {
-        let bytes = Vec::<u8>::read_bits(stream, bits)?;
+        let len = stream.read_var_usize_bits()?;
+        stream.reserve(Reservation::String {
+            len,
+            min_bits: bits,
+        })?;
+
+        let mut bytes = Vec::with_capacity(preallocation::<u8>(len));
+        for _ in 0..len {
+            bytes.push(stream.read_u8_bits(bits)?);
+        }
+
         String::from_utf8(bytes).map_err(|e| e.into())
     }
 }
 
 impl<T: ErasedWriteBitStream> WriteBitStream for T {
     fn write_bool_bits(&mut self, v: bool, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&(v as u8).to_le_bytes(), bits)
+        write_uint_bits(self, v as u64, bits)
     }
 
     fn write_u8_bits(&mut self, v: u8, bits: usize) -> Result<(), Self::Error> {
@@ -334,55 +376,70 @@ impl<T: ErasedWriteBitStream> WriteBitStream for T {
     }
 
     fn write_u16_bits(&mut self, v: u16, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_le_bytes(), bits)
+        write_uint_bits(self, v as u64, bits)
     }
 
     fn write_u32_bits(&mut self, v: u32, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_le_bytes(), bits)
+        write_uint_bits(self, v as u64, bits)
     }
 
     fn write_u64_bits(&mut self, v: u64, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_le_bytes(), bits)
+        write_uint_bits(self, v, bits)
     }
 
     fn write_i8_bits(&mut self, v: i8, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_le_bytes(), bits)
+        write_uint_bits(self, v as u64, bits)
     }
 
     fn write_i16_bits(&mut self, v: i16, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_le_bytes(), bits)
+        write_uint_bits(self, v as u64, bits)
     }
 
     fn write_i32_bits(&mut self, v: i32, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_le_bytes(), bits)
+        write_uint_bits(self, v as u64, bits)
     }
 
     fn write_i64_bits(&mut self, v: i64, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_le_bytes(), bits)
+        write_uint_bits(self, v as u64, bits)
     }
 
     fn write_usize_bits(&mut self, v: usize, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_le_bytes(), bits)
+        self.write_u64_bits(v as u64, bits)
     }
 
     fn write_isize_bits(&mut self, v: isize, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_le_bytes(), bits)
+        self.write_i64_bits(v as i64, bits)
     }
 
     fn write_f32_bits(&mut self, v: f32, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_bits().to_le_bytes(), bits)
+        write_uint_bits(self, v.to_bits() as u64, bits)
     }
 
     fn write_f64_bits(&mut self, v: f64, bits: usize) -> Result<(), Self::Error> {
-        self.write_any_bits(&v.to_bits().to_le_bytes(), bits)
+        write_uint_bits(self, v.to_bits(), bits)
+    }
+
+    fn write_var_u64_bits(&mut self, mut v: u64) -> Result<(), Self::Error> {
+        while v >= 0x80 {
+            self.write_byte_bits(v as u8 | 0x80, 8)?;
+            v >>= 7;
+        }
+
+        self.write_byte_bits(v as u8, 8)
+    }
+
+    fn write_var_i64_bits(&mut self, v: i64) -> Result<(), Self::Error> {
+        self.write_var_u64_bits(zigzag_encode(v))
+    }
+
+    fn write_var_usize_bits(&mut self, v: usize) -> Result<(), Self::Error> {
+        self.write_var_u64_bits(v as u64)
     }
 }
 
 impl<T: ErasedReadBitStream> ReadBitStream for T {
     fn read_bool_bits(&mut self, bits: usize) -> Result<bool, Self::Error> {
-        let mut v = [0u8; size_of::<bool>()];
-        self.read_any_bits(&mut v, bits)?;
-        Ok(v[0] > 0)
+        Ok(read_uint_bits(self, bits)? > 0)
     }
 
     fn read_u8_bits(&mut self, bits: usize) -> Result<u8, Self::Error> {
@@ -390,79 +447,177 @@ impl<T: ErasedReadBitStream> ReadBitStream for T {
     }
 
     fn read_u16_bits(&mut self, bits: usize) -> Result<u16, Self::Error> {
-        let mut v = [0u8; size_of::<u16>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(u16::from_le_bytes(v))
+        Ok(read_uint_bits(self, bits)? as u16)
     }
 
     fn read_u32_bits(&mut self, bits: usize) -> Result<u32, Self::Error> {
-        let mut v = [0u8; size_of::<u32>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(u32::from_le_bytes(v))
+        Ok(read_uint_bits(self, bits)? as u32)
     }
 
     fn read_u64_bits(&mut self, bits: usize) -> Result<u64, Self::Error> {
-        let mut v = [0u8; size_of::<u64>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(u64::from_le_bytes(v))
+        read_uint_bits(self, bits)
     }
 
     fn read_i8_bits(&mut self, bits: usize) -> Result<i8, Self::Error> {
-        let mut v = [0u8; size_of::<i8>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(i8::from_le_bytes(v))
+        Ok(read_uint_bits(self, bits)? as i8)
     }
 
     fn read_i16_bits(&mut self, bits: usize) -> Result<i16, Self::Error> {
-        let mut v = [0u8; size_of::<i16>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(i16::from_le_bytes(v))
+        Ok(read_uint_bits(self, bits)? as i16)
     }
 
     fn read_i32_bits(&mut self, bits: usize) -> Result<i32, Self::Error> {
-        let mut v = [0u8; size_of::<i32>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(i32::from_le_bytes(v))
+        Ok(read_uint_bits(self, bits)? as i32)
     }
 
     fn read_i64_bits(&mut self, bits: usize) -> Result<i64, Self::Error> {
-        let mut v = [0u8; size_of::<i64>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(i64::from_le_bytes(v))
+        Ok(read_uint_bits(self, bits)? as i64)
     }
 
     fn read_usize_bits(&mut self, bits: usize) -> Result<usize, Self::Error> {
-        let mut v = [0u8; size_of::<usize>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(usize::from_le_bytes(v))
+        let v = self.read_u64_bits(bits)?;
+        usize::try_from(v).map_err(|_| SizeOverflow(v).into())
     }
 
     fn read_isize_bits(&mut self, bits: usize) -> Result<isize, Self::Error> {
-        let mut v = [0u8; size_of::<isize>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(isize::from_le_bytes(v))
+        let v = self.read_i64_bits(bits)?;
+        isize::try_from(v).map_err(|_| SizeOverflow(v as u64).into())
     }
 
     fn read_f32_bits(&mut self, bits: usize) -> Result<f32, Self::Error> {
-        let mut v = [0u8; size_of::<f32>()];
-        self.read_any_bits(&mut v, bits)?;
-
-        Ok(f32::from_le_bytes(v))
+        Ok(f32::from_bits(read_uint_bits(self, bits)? as u32))
     }
 
     fn read_f64_bits(&mut self, bits: usize) -> Result<f64, Self::Error> {
-        let mut v = [0u8; size_of::<f64>()];
-        self.read_any_bits(&mut v, bits)?;
+        Ok(f64::from_bits(read_uint_bits(self, bits)?))
+    }
+
+    fn read_var_u64_bits(&mut self) -> Result<u64, Self::Error> {
+        let mut v = 0;
+
+        for shift in (0..u64::BITS).step_by(7) {
+            let byte = self.read_byte_bit(8)?;
+            if shift == 63 && byte > 1 {
+                break;
+            }
+
+            v |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+
+        Err(VarIntOverflow.into())
+    }
+
+    fn read_var_i64_bits(&mut self) -> Result<i64, Self::Error> {
+        Ok(zigzag_decode(self.read_var_u64_bits()?))
+    }
+
+    fn read_var_usize_bits(&mut self) -> Result<usize, Self::Error> {
+        let v = self.read_var_u64_bits()?;
+        usize::try_from(v).map_err(|_| SizeOverflow(v).into())
+    }
+}
+
+/// Writes the lowest `bits` bits of `v`. In big-endian order the most significant (partial)
+/// byte goes first, so full-width values match their `to_be_bytes` layout.
+fn write_uint_bits<T: ErasedWriteBitStream + ?Sized>(
+    stream: &mut T,
+    v: u64,
+    bits: usize,
+) -> Result<(), T::Error> {
+    match stream.byte_order() {
+        ByteOrder::Little => stream.write_any_bits(&v.to_le_bytes(), bits),
+        ByteOrder::Big => {
+            let mut remaining = bits;
+
+            while remaining > 0 {
+                let group = (remaining - 1) % 8 + 1;
+                remaining -= group;
+                stream.write_byte_bits((v >> remaining) as u8 & (0xFF >> (8 - group)), group)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn read_uint_bits<T: ErasedReadBitStream + ?Sized>(
+    stream: &mut T,
+    bits: usize,
+) -> Result<u64, T::Error> {
+    match stream.byte_order() {
+        ByteOrder::Little => {
+            let mut v = [0u8; size_of::<u64>()];
+            stream.read_any_bits(&mut v, bits)?;
+
+            Ok(u64::from_le_bytes(v))
+        }
+        ByteOrder::Big => {
+            let mut v = 0;
+            let mut remaining = bits;
+
+            while remaining > 0 {
+                let group = (remaining - 1) % 8 + 1;
+                remaining -= group;
+                v |= (stream.read_byte_bit(group)? as u64) << remaining;
+            }
+
+            Ok(v)
+        }
+    }
+}
 
-        Ok(f64::from_le_bytes(v))
+#[cfg(test)]
+mod tests {
+    use crate::{
+        io::ByteOrder,
+        net::io::{InputMemoryStream, OutputMemoryStream},
+    };
+
+    use super::{ReadBitStream, WriteBitStream};
+
+    #[test]
+    fn big_endian_bits_put_the_most_significant_group_first() {
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output =
+            OutputMemoryStream::new(&mut buf, &mut ctx).with_byte_order(ByteOrder::Big);
+        output.write_u16_bits(0x0ABC, 12).unwrap();
+        output.write_u32_bits(0x0102_0304, 32).unwrap();
+        output.write_i8_bits(-3, 4).unwrap();
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx).with_byte_order(ByteOrder::Big);
+        assert_eq!(input.read_u16_bits(12).unwrap(), 0x0ABC);
+        assert_eq!(input.read_u32_bits(32).unwrap(), 0x0102_0304);
+        assert_eq!(input.read_u8_bits(4).unwrap(), 0x0D);
+    }
+
+    #[test]
+    fn aligned_big_endian_bits_match_to_be_bytes() {
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output =
+            OutputMemoryStream::new(&mut buf, &mut ctx).with_byte_order(ByteOrder::Big);
+        output.write_u32_bits(0x0102_0304, 32).unwrap();
+        output.write_f64_bits(-0.5, 64).unwrap();
+
+        assert_eq!(buf[..4], 0x0102_0304u32.to_be_bytes());
+        assert_eq!(buf[4..], (-0.5f64).to_be_bytes());
+    }
+
+    #[test]
+    fn little_endian_is_the_default() {
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        output.write_u16_bits(0x0ABC, 12).unwrap();
+        output.write_u32_bits(0x0102_0304, 32).unwrap();
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        assert_eq!(input.read_u8_bits(8).unwrap(), 0xBC);
+        assert_eq!(input.read_u8_bits(4).unwrap(), 0x0A);
+        assert_eq!(input.read_u32_bits(32).unwrap(), 0x0102_0304);
     }
 }
@@ -1,9 +1,15 @@
-use std::{mem::MaybeUninit, string::FromUtf8Error};
+use std::string::FromUtf8Error;
+
+use super::{ByteOrder, Reservation, SizeOverflow, VarIntOverflow, preallocation};
 
 pub trait ErasedWriteStream {
     type Error;
 
     fn write_any(&mut self, v: &[u8]) -> Result<(), Self::Error>;
+
+    fn byte_order(&self) -> ByteOrder {
+        ByteOrder::Little
+    }
 }
 
 pub trait WriteStream: ErasedWriteStream {
@@ -19,17 +25,34 @@ pub trait WriteStream: ErasedWriteStream {
     fn write_i32(&mut self, v: i32) -> Result<(), Self::Error>;
     fn write_i64(&mut self, v: i64) -> Result<(), Self::Error>;
 
+    /// Always 8 bytes on the wire, regardless of the local pointer width.
     fn write_usize(&mut self, v: usize) -> Result<(), Self::Error>;
+    /// Always 8 bytes on the wire, regardless of the local pointer width.
     fn write_isize(&mut self, v: isize) -> Result<(), Self::Error>;
 
     fn write_f32(&mut self, v: f32) -> Result<(), Self::Error>;
     fn write_f64(&mut self, v: f64) -> Result<(), Self::Error>;
+
+    /// LEB128: 7 bits per byte, least significant group first, high bit set on all but the last.
+    fn write_var_u64(&mut self, v: u64) -> Result<(), Self::Error>;
+    /// Zigzag-encoded so that small negative values stay short.
+    fn write_var_i64(&mut self, v: i64) -> Result<(), Self::Error>;
+    fn write_var_usize(&mut self, v: usize) -> Result<(), Self::Error>;
 }
 
 pub trait ErasedReadStream {
-    type Error: From<FromUtf8Error>;
+    type Error: From<FromUtf8Error> + From<VarIntOverflow> + From<SizeOverflow>;
 
     fn read_any(&mut self, v: &mut [u8]) -> Result<(), Self::Error>;
+
+    fn byte_order(&self) -> ByteOrder {
+        ByteOrder::Little
+    }
+
+    /// Called with a length read from the wire before room for it is allocated.
+    fn reserve(&mut self, _reservation: Reservation) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
 
 pub trait ReadStream: ErasedReadStream {
@@ -50,6 +73,10 @@ pub trait ReadStream: ErasedReadStream {
 
     fn read_f32(&mut self) -> Result<f32, Self::Error>;
     fn read_f64(&mut self) -> Result<f64, Self::Error>;
+
+    fn read_var_u64(&mut self) -> Result<u64, Self::Error>;
+    fn read_var_i64(&mut self) -> Result<i64, Self::Error>;
+    fn read_var_usize(&mut self) -> Result<usize, Self::Error>;
 }
 
 pub trait Readable<R: ReadStream>: Sized {
@@ -189,7 +216,7 @@ impl<W: WriteStream, T: Writable<W>, const N: usize> Writable<W> for [T; N] {
 
 impl<W: WriteStream, T: Writable<W>> Writable<W> for &[T] {
     fn write_byte(&self, stream: &mut W) -> Result<(), W::Error> {
-        stream.write_usize(self.len())?;
+        stream.write_var_usize(self.len())?;
 
         for el in self.iter() {
             el.write_byte(stream)?;
@@ -201,7 +228,7 @@ impl<W: WriteStream, T: Writable<W>> Writable<W> for &[T] {
 
 impl<W: WriteStream, T: Writable<W>> Writable<W> for Vec<T> {
     fn write_byte(&self, stream: &mut W) -> Result<(), W::Error> {
-        stream.write_usize(self.len())?;
+        stream.write_var_usize(self.len())?;
 
         for el in self {
             el.write_byte(stream)?;
@@ -384,8 +411,10 @@ impl<R: ReadStream, T: Readable<R>> Readable<R> for Option<T> {
 
 impl<R: ReadStream, T: Readable<R>> Readable<R> for Vec<T> {
     fn read_byte(stream: &mut R) -> Result<Self, R::Error> {
-        let len = stream.read_usize()?;
-        let mut vec = Vec::with_capacity(len);
+        let len = stream.read_var_usize()?;
+        stream.reserve(Reservation::collection::<T>(len, 8))?;
+
+        let mut vec = Vec::with_capacity(preallocation::<T>(len));
         for _ in 0..len {
             vec.push(T::read_byte(stream)?);
         }
@@ -401,7 +430,16 @@ impl<R: ReadStream, T: Readable<R>, const N: usize> Readable<R> for [T; N] {
 
 impl<R: ReadStream> Readable<R> for String {
     fn read_byte(stream: &mut R) -> Result<Self, R::Error> {
-        let bytes = Vec::<u8>::read_byte(stream)?;
+        let len = stream.read_var_usize()?;
+        stream.reserve(Reservation::String { len, min_bits: 8 })?;
+
+        let mut bytes = Vec::with_capacity(preallocation::<u8>(len));
+        while bytes.len() < len {
+            let start = bytes.len();
+            bytes.resize(start + preallocation::<u8>(len - start), 0);
+            stream.read_any(&mut bytes[start..])?;
+        }
+
         String::from_utf8(bytes).map_err(|e| e.into())
     }
 }
@@ -473,51 +511,68 @@ impl<T: ErasedWriteStream> WriteStream for T {
     }
 
     fn write_u8(&mut self, v: u8) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_le_bytes()))
     }
 
     fn write_u16(&mut self, v: u16) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_le_bytes()))
     }
 
     fn write_u32(&mut self, v: u32) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_le_bytes()))
     }
 
     fn write_u64(&mut self, v: u64) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_le_bytes()))
     }
 
     fn write_i8(&mut self, v: i8) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_le_bytes()))
     }
 
     fn write_i16(&mut self, v: i16) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_le_bytes()))
     }
 
     fn write_i32(&mut self, v: i32) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_le_bytes()))
     }
 
     fn write_i64(&mut self, v: i64) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_le_bytes()))
     }
 
     fn write_usize(&mut self, v: usize) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_u64(v as u64)
     }
 
     fn write_isize(&mut self, v: isize) -> Result<(), Self::Error> {
-        self.write_any(&v.to_le_bytes())
+        self.write_i64(v as i64)
     }
 
     fn write_f32(&mut self, v: f32) -> Result<(), Self::Error> {
-        self.write_any(&v.to_bits().to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_bits().to_le_bytes()))
     }
 
     fn write_f64(&mut self, v: f64) -> Result<(), Self::Error> {
-        self.write_any(&v.to_bits().to_le_bytes())
+        self.write_any(&self.byte_order().reorder(v.to_bits().to_le_bytes()))
+    }
+
+    fn write_var_u64(&mut self, mut v: u64) -> Result<(), Self::Error> {
+        while v >= 0x80 {
+            self.write_u8(v as u8 | 0x80)?;
+            v >>= 7;
+        }
+
+        self.write_u8(v as u8)
+    }
+
+    fn write_var_i64(&mut self, v: i64) -> Result<(), Self::Error> {
+        self.write_var_u64(zigzag_encode(v))
+    }
+
+    fn write_var_usize(&mut self, v: usize) -> Result<(), Self::Error> {
+        self.write_var_u64(v as u64)
     }
 }
 
@@ -532,83 +587,216 @@ impl<T: ErasedReadStream> ReadStream for T {
         let mut v = [0u8; size_of::<u8>()];
         self.read_any(&mut v)?;
 
-        Ok(u8::from_le_bytes(v))
+        Ok(u8::from_le_bytes(self.byte_order().reorder(v)))
     }
 
     fn read_u16(&mut self) -> Result<u16, Self::Error> {
         let mut v = [0u8; size_of::<u16>()];
         self.read_any(&mut v)?;
 
-        Ok(u16::from_le_bytes(v))
+        Ok(u16::from_le_bytes(self.byte_order().reorder(v)))
     }
 
     fn read_u32(&mut self) -> Result<u32, Self::Error> {
         let mut v = [0u8; size_of::<u32>()];
         self.read_any(&mut v)?;
 
-        Ok(u32::from_le_bytes(v))
+        Ok(u32::from_le_bytes(self.byte_order().reorder(v)))
     }
 
     fn read_u64(&mut self) -> Result<u64, Self::Error> {
         let mut v = [0u8; size_of::<u64>()];
         self.read_any(&mut v)?;
 
-        Ok(u64::from_le_bytes(v))
+        Ok(u64::from_le_bytes(self.byte_order().reorder(v)))
     }
 
     fn read_i8(&mut self) -> Result<i8, Self::Error> {
         let mut v = [0u8; size_of::<i8>()];
         self.read_any(&mut v)?;
 
-        Ok(i8::from_le_bytes(v))
+        Ok(i8::from_le_bytes(self.byte_order().reorder(v)))
     }
 
     fn read_i16(&mut self) -> Result<i16, Self::Error> {
         let mut v = [0u8; size_of::<i16>()];
         self.read_any(&mut v)?;
 
-        Ok(i16::from_le_bytes(v))
+        Ok(i16::from_le_bytes(self.byte_order().reorder(v)))
     }
 
     fn read_i32(&mut self) -> Result<i32, Self::Error> {
         let mut v = [0u8; size_of::<i32>()];
         self.read_any(&mut v)?;
 
-        Ok(i32::from_le_bytes(v))
+        Ok(i32::from_le_bytes(self.byte_order().reorder(v)))
     }
 
     fn read_i64(&mut self) -> Result<i64, Self::Error> {
         let mut v = [0u8; size_of::<i64>()];
         self.read_any(&mut v)?;
 
-        Ok(i64::from_le_bytes(v))
+        Ok(i64::from_le_bytes(self.byte_order().reorder(v)))
     }
 
     fn read_usize(&mut self) -> Result<usize, Self::Error> {
-        let mut v = [0u8; size_of::<usize>()];
-        self.read_any(&mut v)?;
-
-        Ok(usize::from_le_bytes(v))
+        let v = self.read_u64()?;
+        usize::try_from(v).map_err(|_| SizeOverflow(v).into())
     }
 
     fn read_isize(&mut self) -> Result<isize, Self::Error> {
-        let mut v = [0u8; size_of::<isize>()];
-        self.read_any(&mut v)?;
-
-        Ok(isize::from_le_bytes(v))
+        let v = self.read_i64()?;
+        isize::try_from(v).map_err(|_| SizeOverflow(v as u64).into())
     }
 
     fn read_f32(&mut self) -> Result<f32, Self::Error> {
         let mut v = [0u8; size_of::<f32>()];
         self.read_any(&mut v)?;
 
-        Ok(f32::from_le_bytes(v))
+        Ok(f32::from_le_bytes(self.byte_order().reorder(v)))
     }
 
     fn read_f64(&mut self) -> Result<f64, Self::Error> {
         let mut v = [0u8; size_of::<f64>()];
         self.read_any(&mut v)?;
 
-        Ok(f64::from_le_bytes(v))
+        Ok(f64::from_le_bytes(self.byte_order().reorder(v)))
+    }
+
+    fn read_var_u64(&mut self) -> Result<u64, Self::Error> {
+        let mut v = 0;
+
+        for shift in (0..u64::BITS).step_by(7) {
+            let byte = self.read_u8()?;
+            if shift == 63 && byte > 1 {
+                break;
+            }
+
+            v |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+
+        Err(VarIntOverflow.into())
+    }
+
+    fn read_var_i64(&mut self) -> Result<i64, Self::Error> {
+        Ok(zigzag_decode(self.read_var_u64()?))
+    }
+
+    fn read_var_usize(&mut self) -> Result<usize, Self::Error> {
+        let v = self.read_var_u64()?;
+        usize::try_from(v).map_err(|_| SizeOverflow(v).into())
+    }
+}
+
+pub(crate) fn zigzag_encode(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+pub(crate) fn zigzag_decode(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        io::ByteOrder,
+        net::io::{DecodeLimits, GameIoError, InputMemoryStream, OutputMemoryStream},
+    };
+
+    use super::{ReadStream, Readable, Writable, WriteStream};
+
+    #[test]
+    fn pointer_sized_integers_are_eight_bytes() {
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        output.write_usize(0x0102_0304).unwrap();
+        output.write_isize(-2).unwrap();
+
+        assert_eq!(
+            buf,
+            [
+                0x04, 0x03, 0x02, 0x01, 0, 0, 0, 0, //
+                0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ]
+        );
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        assert_eq!(input.read_usize().unwrap(), 0x0102_0304);
+        assert_eq!(input.read_isize().unwrap(), -2);
+    }
+
+    #[test]
+    fn usize_too_large_for_this_platform_is_rejected() {
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        output.write_u64(u64::MAX).unwrap();
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        let result = input.read_usize();
+
+        if usize::BITS < u64::BITS {
+            assert!(matches!(result, Err(GameIoError::SizeOverflow(u64::MAX))));
+        } else {
+            assert_eq!(result.unwrap() as u64, u64::MAX);
+        }
+    }
+
+    #[test]
+    fn huge_length_prefixes_fail_without_allocating_up_front() {
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        output.write_var_u64(u64::MAX >> 2).unwrap();
+        output.write_u64(7).unwrap();
+
+        let unlimited = DecodeLimits {
+            max_collection_len: usize::MAX,
+            max_string_bytes: usize::MAX,
+            max_allocation: usize::MAX,
+        };
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx).with_limits(unlimited);
+        let result = Vec::<glam::Mat4>::read_byte(&mut input);
+        assert!(matches!(result, Err(GameIoError::UnexpectedEof(..))));
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx).with_limits(unlimited);
+        let result = String::read_byte(&mut input);
+        assert!(matches!(result, Err(GameIoError::UnexpectedEof(..))));
+    }
+
+    #[test]
+    fn big_endian_streams_reverse_fixed_width_values() {
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output =
+            OutputMemoryStream::new(&mut buf, &mut ctx).with_byte_order(ByteOrder::Big);
+        output.write_u32(0x0102_0304).unwrap();
+        output.write_i16(-2).unwrap();
+        output.write_f32(1.0).unwrap();
+        output.write_usize(5).unwrap();
+        vec![0x0A0Bu16].write_byte(&mut output).unwrap();
+
+        assert_eq!(
+            buf,
+            [
+                0x01, 0x02, 0x03, 0x04, //
+                0xFF, 0xFE, //
+                0x3F, 0x80, 0x00, 0x00, //
+                0, 0, 0, 0, 0, 0, 0, 5, //
+                1, 0x0A, 0x0B,
+            ]
+        );
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx).with_byte_order(ByteOrder::Big);
+        assert_eq!(input.read_u32().unwrap(), 0x0102_0304);
+        assert_eq!(input.read_i16().unwrap(), -2);
+        assert_eq!(input.read_f32().unwrap(), 1.0);
+        assert_eq!(input.read_usize().unwrap(), 5);
+        assert_eq!(Vec::<u16>::read_byte(&mut input).unwrap(), [0x0A0B]);
     }
 }
@@ -1,2 +1,74 @@
 pub mod bits;
 pub mod bytes;
+pub mod quantized;
+pub mod varint;
+
+#[cfg(test)]
+mod tests;
+
+/// Byte order of fixed-width values in byte and bit streams. Varints are always LEB128.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    /// Converts little-endian bytes to this order, or back.
+    pub fn reorder<const N: usize>(self, mut bytes: [u8; N]) -> [u8; N] {
+        if self == ByteOrder::Big {
+            bytes.reverse();
+        }
+
+        bytes
+    }
+}
+
+/// Upper bound, in bytes, on the space reserved up front for a length-prefixed collection. The
+/// length comes off the wire, so anything past this grows as elements are actually read.
+pub(crate) const MAX_PREALLOCATION: usize = 4096;
+
+/// Number of `T`s to reserve for a collection of `len` elements read from the wire.
+pub(crate) fn preallocation<T>(len: usize) -> usize {
+    len.min(MAX_PREALLOCATION / size_of::<T>().max(1))
+}
+
+/// A length-prefixed value that a reader is about to allocate room for, see
+/// [`bytes::ErasedReadStream::reserve`]. Streams over untrusted input use it to reject lengths
+/// before anything is allocated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reservation {
+    /// `len` elements of `size` bytes each, every one taking at least `min_bits` on the wire.
+    Collection {
+        len: usize,
+        size: usize,
+        min_bits: usize,
+    },
+    /// A string of `len` bytes, every one taking at least `min_bits` on the wire.
+    String { len: usize, min_bits: usize },
+}
+
+impl Reservation {
+    /// A collection of `len` `T`s, each taking at least `min_bits` on the wire unless `T` is
+    /// zero-sized.
+    pub fn collection<T>(len: usize, min_bits: usize) -> Self {
+        let size = size_of::<T>();
+        let min_bits = if size == 0 { 0 } else { min_bits };
+
+        Self::Collection {
+            len,
+            size,
+            min_bits,
+        }
+    }
+}
+
+/// A varint that is malformed or does not fit the integer type it is decoded into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VarIntOverflow;
+
+/// A pointer-sized integer read from the wire that does not fit the local `usize` or `isize`.
+/// Holds the raw 64-bit wire value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SizeOverflow(pub u64);
@@ -0,0 +1,307 @@
+use crate::utils::{f64_to_fixed, fixed_to_f64};
+
+use super::bits::{BitReadable, BitWritable, ReadBitStream, WriteBitStream};
+
+/// An `f32` sent as a `BITS`-bit fixed-point number in `MIN..=MAX`. Values outside the range are
+/// clamped and the rest are rounded to the nearest step, so the decoded value is within
+/// [`Quantized::MAX_ERROR`] of the clamped input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Quantized<const MIN: i32, const MAX: i32, const BITS: u32>(pub f32);
+
+/// A `glam::Vec2` with each component sent as a [`Quantized`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuantizedVec2<const MIN: i32, const MAX: i32, const BITS: u32>(pub glam::Vec2);
+
+/// A `glam::Vec3` with each component sent as a [`Quantized`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuantizedVec3<const MIN: i32, const MAX: i32, const BITS: u32>(pub glam::Vec3);
+
+impl<const MIN: i32, const MAX: i32, const BITS: u32> Quantized<MIN, MAX, BITS> {
+    const MAX_CODE: u64 = {
+        assert!(MIN < MAX, "empty quantization range");
+        assert!(BITS > 0 && BITS <= 32, "quantized values use 1 to 32 bits");
+
+        (1 << BITS) - 1
+    };
+
+    /// Distance between two representable values.
+    pub const PRECISION: f64 = (MAX as f64 - MIN as f64) / Self::MAX_CODE as f64;
+
+    /// Largest difference between an in-range value and its decoded counterpart.
+    pub const MAX_ERROR: f64 = Self::PRECISION / 2.0;
+
+    fn encode(v: f32) -> u64 {
+        let v = (v as f64).clamp(MIN as f64, MAX as f64);
+        f64_to_fixed(v, MIN as f64, Self::PRECISION).min(Self::MAX_CODE)
+    }
+
+    fn decode(code: u64) -> f32 {
+        fixed_to_f64(code.min(Self::MAX_CODE), MIN as f64, Self::PRECISION) as f32
+    }
+}
+
+impl<W: WriteBitStream, const MIN: i32, const MAX: i32, const BITS: u32> BitWritable<W>
+    for Quantized<MIN, MAX, BITS>
+{
+    fn write_bits(&self, stream: &mut W, _bits: usize) -> Result<(), W::Error> {
+        stream.write_u64_bits(Self::encode(self.0), BITS as usize)
+    }
+}
+
+impl<R: ReadBitStream, const MIN: i32, const MAX: i32, const BITS: u32> BitReadable<R>
+    for Quantized<MIN, MAX, BITS>
+{
+    fn read_bits(stream: &mut R, _bits: usize) -> Result<Self, R::Error> {
+        Ok(Self(Self::decode(stream.read_u64_bits(BITS as usize)?)))
+    }
+}
+
+impl<W: WriteBitStream, const MIN: i32, const MAX: i32, const BITS: u32> BitWritable<W>
+    for QuantizedVec2<MIN, MAX, BITS>
+{
+    fn write_bits(&self, stream: &mut W, bits: usize) -> Result<(), W::Error> {
+        for v in self.0.to_array() {
+            Quantized::<MIN, MAX, BITS>(v).write_bits(stream, bits)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: ReadBitStream, const MIN: i32, const MAX: i32, const BITS: u32> BitReadable<R>
+    for QuantizedVec2<MIN, MAX, BITS>
+{
+    fn read_bits(stream: &mut R, bits: usize) -> Result<Self, R::Error> {
+        let v = std::array::try_from_fn(|_| {
+            Quantized::<MIN, MAX, BITS>::read_bits(stream, bits).map(|v| v.0)
+        })?;
+
+        Ok(Self(glam::Vec2::from_array(v)))
+    }
+}
+
+impl<W: WriteBitStream, const MIN: i32, const MAX: i32, const BITS: u32> BitWritable<W>
+    for QuantizedVec3<MIN, MAX, BITS>
+{
+    fn write_bits(&self, stream: &mut W, bits: usize) -> Result<(), W::Error> {
+        for v in self.0.to_array() {
+            Quantized::<MIN, MAX, BITS>(v).write_bits(stream, bits)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: ReadBitStream, const MIN: i32, const MAX: i32, const BITS: u32> BitReadable<R>
+    for QuantizedVec3<MIN, MAX, BITS>
+{
+    fn read_bits(stream: &mut R, bits: usize) -> Result<Self, R::Error> {
+        let v = std::array::try_from_fn(|_| {
+            Quantized::<MIN, MAX, BITS>::read_bits(stream, bits).map(|v| v.0)
+        })?;
+
+        Ok(Self(glam::Vec3::from_array(v)))
+    }
+}
+
+/// A unit `glam::Quat` sent as "smallest three": the 2-bit index of the largest component,
+/// followed by the other three quantized to `BITS` bits each. The largest component is rebuilt
+/// from the unit length on read.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CompressedQuat<const BITS: u32>(pub glam::Quat);
+
+impl<const BITS: u32> CompressedQuat<BITS> {
+    /// An even step count keeps zero representable, so axis-aligned rotations are exact.
+    const MAX_CODE: u64 = {
+        assert!(
+            BITS > 1 && BITS <= 32,
+            "compressed quaternions use 2 to 32 bits"
+        );
+
+        (1 << BITS) - 2
+    };
+
+    /// The three smallest components of a unit quaternion are within this range.
+    const RANGE: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+    /// Distance between two representable component values.
+    pub const PRECISION: f64 = 2.0 * Self::RANGE / Self::MAX_CODE as f64;
+
+    fn encode(v: f32) -> u64 {
+        let v = (v as f64).clamp(-Self::RANGE, Self::RANGE);
+        f64_to_fixed(v, -Self::RANGE, Self::PRECISION).min(Self::MAX_CODE)
+    }
+
+    fn decode(code: u64) -> f32 {
+        fixed_to_f64(code.min(Self::MAX_CODE), -Self::RANGE, Self::PRECISION) as f32
+    }
+}
+
+impl<W: WriteBitStream, const BITS: u32> BitWritable<W> for CompressedQuat<BITS> {
+    fn write_bits(&self, stream: &mut W, _bits: usize) -> Result<(), W::Error> {
+        let q = self.0.normalize();
+        let mut q = if q.is_finite() {
+            q
+        } else {
+            glam::Quat::IDENTITY
+        }
+        .to_array();
+
+        let largest = (0..4)
+            .max_by(|&a, &b| q[a].abs().total_cmp(&q[b].abs()))
+            .unwrap();
+
+        // `q` and `-q` are the same rotation, flip so the dropped component is positive.
+        if q[largest] < 0.0 {
+            q = q.map(|v| -v);
+        }
+
+        stream.write_u8_bits(largest as u8, 2)?;
+        for (idx, v) in q.into_iter().enumerate() {
+            if idx != largest {
+                stream.write_u64_bits(Self::encode(v), BITS as usize)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: ReadBitStream, const BITS: u32> BitReadable<R> for CompressedQuat<BITS> {
+    fn read_bits(stream: &mut R, _bits: usize) -> Result<Self, R::Error> {
+        let largest = stream.read_u8_bits(2)? as usize;
+        let mut q = [0.0f32; 4];
+        let mut sum = 0.0;
+
+        for (idx, v) in q.iter_mut().enumerate() {
+            if idx != largest {
+                *v = Self::decode(stream.read_u64_bits(BITS as usize)?);
+                sum += *v * *v;
+            }
+        }
+
+        q[largest] = (1.0 - sum).max(0.0).sqrt();
+
+        Ok(Self(glam::Quat::from_array(q).normalize()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        io::bits::{BitReadable, BitWritable},
+        net::io::{InputMemoryStream, OutputMemoryStream},
+    };
+
+    use super::{CompressedQuat, Quantized, QuantizedVec2, QuantizedVec3};
+
+    type Position = Quantized<-1000, 1000, 20>;
+
+    fn round_trip<T>(value: T) -> (T, usize)
+    where
+        T: for<'a, 'b> BitWritable<OutputMemoryStream<'a, 'b, ()>>
+            + for<'a, 'b> BitReadable<InputMemoryStream<'a, 'b, ()>>,
+    {
+        let mut buf = vec![];
+        let mut ctx = ();
+        value
+            .write_bits(&mut OutputMemoryStream::new(&mut buf, &mut ctx), 0)
+            .unwrap();
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        let read = T::read_bits(&mut input, 0).unwrap();
+        (read, buf.len() * 8 - input.remaining_bit_count())
+    }
+
+    fn position(v: f32) -> f32 {
+        let (read, bits) = round_trip::<Position>(Quantized(v));
+        assert_eq!(bits, 20);
+        read.0
+    }
+
+    #[test]
+    fn decoded_values_stay_within_half_a_step() {
+        // Allow for the f32 rounding of the decoded value on top of the quantization error.
+        let max_error = Position::MAX_ERROR as f32 + 1000.0 * f32::EPSILON;
+
+        for i in 0..=20_000 {
+            let v = -1000.0 + i as f32 * 0.099_97;
+            let read = position(v);
+            assert!((read - v).abs() <= max_error, "{v} decoded as {read}");
+        }
+    }
+
+    #[test]
+    fn range_ends_are_exact() {
+        assert_eq!(position(-1000.0), -1000.0);
+        assert_eq!(position(1000.0), 1000.0);
+    }
+
+    #[test]
+    fn out_of_range_values_are_clamped() {
+        assert_eq!(position(5000.0), 1000.0);
+        assert_eq!(position(-5000.0), -1000.0);
+        assert_eq!(position(f32::INFINITY), 1000.0);
+        assert_eq!(round_trip(Quantized::<0, 1, 1>(0.75)).0.0, 1.0);
+    }
+
+    #[test]
+    fn vectors_quantize_each_component() {
+        let max_error = Quantized::<-64, 64, 12>::MAX_ERROR as f32 + 1e-5;
+        let velocity = QuantizedVec3::<-64, 64, 12>(glam::Vec3::new(-63.9, 0.01, 100.0));
+        let (read, bits) = round_trip(velocity);
+
+        assert_eq!(bits, 3 * 12);
+        assert!(
+            read.0
+                .abs_diff_eq(glam::Vec3::new(-63.9, 0.01, 64.0), max_error)
+        );
+
+        let max_error = Quantized::<0, 10, 8>::MAX_ERROR as f32 + 1e-6;
+        let uv = QuantizedVec2::<0, 10, 8>(glam::Vec2::new(2.5, 7.5));
+        let (read, bits) = round_trip(uv);
+
+        assert_eq!(bits, 2 * 8);
+        assert!(read.0.abs_diff_eq(uv.0, max_error));
+    }
+
+    #[test]
+    fn compressed_quat_angular_error_is_bounded() {
+        let mut max_angle = 0.0f32;
+
+        for yaw in 0..24 {
+            for pitch in 0..12 {
+                for roll in 0..12 {
+                    let q = glam::Quat::from_euler(
+                        glam::EulerRot::YXZ,
+                        yaw as f32 * 0.27,
+                        pitch as f32 * 0.53 - 3.0,
+                        roll as f32 * 0.51,
+                    );
+                    let (read, bits) = round_trip(CompressedQuat::<10>(q));
+
+                    assert_eq!(bits, 2 + 3 * 10);
+                    max_angle = max_angle.max(read.0.angle_between(q));
+                }
+            }
+        }
+
+        assert!(
+            max_angle < 0.25f32.to_radians(),
+            "{}",
+            max_angle.to_degrees()
+        );
+    }
+
+    #[test]
+    fn compressed_quat_handles_sign_and_degenerate_input() {
+        let q = glam::Quat::from_rotation_x(2.0);
+        let (read, _) = round_trip(CompressedQuat::<12>(-q));
+        assert!(read.0.angle_between(q) < 0.1f32.to_radians());
+
+        let (read, _) = round_trip(CompressedQuat::<12>(glam::Quat::from_xyzw(
+            0.0, 0.0, 0.0, 0.0,
+        )));
+        assert_eq!(read.0, glam::Quat::IDENTITY);
+    }
+}
@@ -0,0 +1,239 @@
+//! Round trips for every `Writable`/`Readable` and `BitWritable`/`BitReadable` impl in
+//! [`crate::io`], plus golden encodings that pin down the wire format.
+
+use std::fmt::Debug;
+
+use proptest::prelude::*;
+
+use crate::net::io::{InputMemoryStream, OutputMemoryStream};
+
+use super::{
+    bits::{BitReadable, BitWritable, ReadBitStream, WriteBitStream},
+    bytes::{Readable, Writable},
+};
+
+type Output<'a, 'b> = OutputMemoryStream<'a, 'b, ()>;
+type Input<'a, 'b> = InputMemoryStream<'a, 'b, ()>;
+
+/// Junk written before the value so that it starts at `offset` bits into a byte.
+const PADDING: u8 = 0b1010_0101;
+
+fn padding(offset: usize) -> u8 {
+    (PADDING as u16 & ((1 << offset) - 1)) as u8
+}
+
+fn byte_round_trip<T>(value: &T, offset: usize) -> T
+where
+    T: for<'a, 'b> Writable<Output<'a, 'b>> + for<'a, 'b> Readable<Input<'a, 'b>>,
+{
+    let mut buf = vec![];
+    let mut ctx = ();
+    let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+    output.write_u8_bits(PADDING, offset).unwrap();
+    value.write_byte(&mut output).unwrap();
+
+    let mut input = InputMemoryStream::new(&buf, &mut ctx);
+    assert_eq!(input.read_u8_bits(offset).unwrap(), padding(offset));
+    let read = T::read_byte(&mut input).unwrap();
+    assert!(input.remaining_bit_count() < 8);
+
+    read
+}
+
+fn bit_round_trip<T>(value: &T, offset: usize, bits: usize) -> T
+where
+    T: for<'a, 'b> BitWritable<Output<'a, 'b>> + for<'a, 'b> BitReadable<Input<'a, 'b>>,
+{
+    let mut buf = vec![];
+    let mut ctx = ();
+    let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+    output.write_u8_bits(PADDING, offset).unwrap();
+    value.write_bits(&mut output, bits).unwrap();
+
+    let mut input = InputMemoryStream::new(&buf, &mut ctx);
+    assert_eq!(input.read_u8_bits(offset).unwrap(), padding(offset));
+    let read = T::read_bits(&mut input, bits).unwrap();
+    assert!(input.remaining_bit_count() < 8);
+
+    read
+}
+
+fn encode<T: for<'a, 'b> Writable<Output<'a, 'b>> + ?Sized>(value: &T) -> Vec<u8> {
+    let mut buf = vec![];
+    value
+        .write_byte(&mut OutputMemoryStream::new(&mut buf, &mut ()))
+        .unwrap();
+
+    buf
+}
+
+fn assert_same_bits<T: Debug>(lhs: &T, rhs: &T, bits: impl Fn(&T) -> Vec<u32>) {
+    assert_eq!(bits(lhs), bits(rhs), "{lhs:?} != {rhs:?}");
+}
+
+macro_rules! int_round_trips {
+    ($($name:ident: $ty:ty),* $(,)?) => {
+        proptest! {
+            $(
+                #[test]
+                fn $name(
+                    v in any::<$ty>(),
+                    offset in 0..8usize,
+                    bits in 1..=<$ty>::BITS as usize,
+                ) {
+                    prop_assert_eq!(byte_round_trip(&v, offset), v);
+
+                    let mask = u64::MAX >> (64 - bits);
+                    prop_assert_eq!(bit_round_trip(&v, offset, bits), (v as u64 & mask) as $ty);
+                }
+            )*
+        }
+    };
+}
+
+int_round_trips! {
+    u8_round_trips: u8,
+    u16_round_trips: u16,
+    u32_round_trips: u32,
+    u64_round_trips: u64,
+    usize_round_trips: usize,
+    i8_round_trips: i8,
+    i16_round_trips: i16,
+    i32_round_trips: i32,
+    i64_round_trips: i64,
+    isize_round_trips: isize,
+}
+
+fn any_vec2() -> impl Strategy<Value = glam::Vec2> {
+    any::<[f32; 2]>().prop_map(glam::Vec2::from_array)
+}
+
+fn any_vec3() -> impl Strategy<Value = glam::Vec3> {
+    any::<[f32; 3]>().prop_map(glam::Vec3::from_array)
+}
+
+fn any_vec4() -> impl Strategy<Value = glam::Vec4> {
+    any::<[f32; 4]>().prop_map(glam::Vec4::from_array)
+}
+
+proptest! {
+    #[test]
+    fn bool_round_trips(v in any::<bool>(), offset in 0..8usize, bits in 1..=8usize) {
+        prop_assert_eq!(byte_round_trip(&v, offset), v);
+        prop_assert_eq!(bit_round_trip(&v, offset, bits), v);
+    }
+
+    #[test]
+    fn floats_round_trip_bit_exactly(
+        a in any::<u32>().prop_map(f32::from_bits),
+        b in any::<u64>().prop_map(f64::from_bits),
+        offset in 0..8usize,
+    ) {
+        prop_assert_eq!(byte_round_trip(&a, offset).to_bits(), a.to_bits());
+        prop_assert_eq!(bit_round_trip(&a, offset, 32).to_bits(), a.to_bits());
+        prop_assert_eq!(byte_round_trip(&b, offset).to_bits(), b.to_bits());
+        prop_assert_eq!(bit_round_trip(&b, offset, 64).to_bits(), b.to_bits());
+    }
+
+    #[test]
+    fn options_round_trip(v in any::<Option<u16>>(), offset in 0..8usize, bits in 1..=16usize) {
+        prop_assert_eq!(byte_round_trip(&v, offset), v);
+        prop_assert_eq!(
+            bit_round_trip(&v, offset, bits),
+            v.map(|v| v & (u16::MAX >> (16 - bits)))
+        );
+    }
+
+    #[test]
+    fn vecs_round_trip(
+        v in prop::collection::vec(any::<i32>(), 0..300),
+        offset in 0..8usize,
+    ) {
+        prop_assert_eq!(byte_round_trip(&v, offset), v.clone());
+        prop_assert_eq!(bit_round_trip(&v, offset, 32), v);
+    }
+
+    #[test]
+    fn nested_containers_round_trip(
+        v in prop::collection::vec(any::<Option<[u8; 3]>>(), 0..8),
+        offset in 0..8usize,
+    ) {
+        prop_assert_eq!(byte_round_trip(&v, offset), v);
+    }
+
+    #[test]
+    fn arrays_round_trip(v in any::<[i64; 5]>(), offset in 0..8usize) {
+        prop_assert_eq!(byte_round_trip(&v, offset), v);
+    }
+
+    #[test]
+    fn strings_round_trip(v in any::<String>(), offset in 0..8usize) {
+        prop_assert_eq!(byte_round_trip(&v, offset), v.clone());
+        prop_assert_eq!(bit_round_trip(&v, offset, 8), v);
+    }
+
+    #[test]
+    fn glam_vectors_round_trip(
+        a in any_vec2(),
+        b in any_vec3(),
+        c in any_vec4(),
+        offset in 0..8usize,
+    ) {
+        assert_same_bits(&byte_round_trip(&a, offset), &a, |v| v.to_array().map(f32::to_bits).to_vec());
+        assert_same_bits(&byte_round_trip(&b, offset), &b, |v| v.to_array().map(f32::to_bits).to_vec());
+        assert_same_bits(&byte_round_trip(&c, offset), &c, |v| v.to_array().map(f32::to_bits).to_vec());
+    }
+
+    #[test]
+    fn glam_matrices_and_quats_round_trip(
+        a in any::<[f32; 4]>().prop_map(|v| glam::Mat2::from_cols_array(&v)),
+        b in any::<[f32; 9]>().prop_map(|v| glam::Mat3::from_cols_array(&v)),
+        c in any::<[f32; 16]>().prop_map(|v| glam::Mat4::from_cols_array(&v)),
+        d in any::<[f32; 4]>().prop_map(glam::Quat::from_array),
+        offset in 0..8usize,
+    ) {
+        assert_same_bits(&byte_round_trip(&a, offset), &a, |v| v.to_cols_array().map(f32::to_bits).to_vec());
+        assert_same_bits(&byte_round_trip(&b, offset), &b, |v| v.to_cols_array().map(f32::to_bits).to_vec());
+        assert_same_bits(&byte_round_trip(&c, offset), &c, |v| v.to_cols_array().map(f32::to_bits).to_vec());
+        assert_same_bits(&byte_round_trip(&d, offset), &d, |v| v.to_array().map(f32::to_bits).to_vec());
+    }
+}
+
+#[test]
+fn golden_byte_encodings() {
+    assert_eq!(encode(&true), [0x01]);
+    assert_eq!(encode(&0x0102_0304u32), [0x04, 0x03, 0x02, 0x01]);
+    assert_eq!(encode(&-2i16), [0xFE, 0xFF]);
+    assert_eq!(encode(&1.0f32), [0x00, 0x00, 0x80, 0x3F]);
+    assert_eq!(encode(&f32::NAN), f32::NAN.to_bits().to_le_bytes());
+    assert_eq!(encode(&5usize), [5, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(encode(&Some(7u8)), [0x01, 0x07]);
+    assert_eq!(encode(&None::<u8>), [0x00]);
+    assert_eq!(encode(&vec![1u16, 2]), [0x02, 0x01, 0x00, 0x02, 0x00]);
+    assert_eq!(encode(&[3u8, 4]), [0x03, 0x04]);
+    assert_eq!(encode(&"hi".to_string()), [0x02, b'h', b'i']);
+    assert_eq!(encode(&vec![0u8; 200])[..2], [0xC8, 0x01]);
+    assert_eq!(
+        encode(&glam::Vec2::new(1.0, -2.0)),
+        [0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x00, 0xC0]
+    );
+    assert_eq!(
+        encode(&glam::Quat::IDENTITY),
+        [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x00, 0x80, 0x3F]
+    );
+}
+
+#[test]
+fn golden_bit_encodings() {
+    let mut buf = vec![];
+    let mut ctx = ();
+    let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+    output.write_u8_bits(0b101, 3).unwrap();
+    output.write_u16_bits(0x1FF, 9).unwrap();
+    Some(3u8).write_bits(&mut output, 2).unwrap();
+    "a".write_bits(&mut output, 8).unwrap();
+    output.write_bool_bits(true, 1).unwrap();
+
+    // 101 | 1_1111_1111 | 1 11 | 0000_0001 | 0110_0001 | 1, filled from the least significant bit.
+    assert_eq!(buf, [0xFD, 0xFF, 0x80, 0xB0]);
+}
@@ -0,0 +1,218 @@
+use super::{
+    VarIntOverflow,
+    bits::{BitReadable, BitWritable, ReadBitStream, WriteBitStream},
+    bytes::{ReadStream, Readable, Writable, WriteStream},
+};
+
+/// Unsigned integers written as LEB128 varints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarU32(pub u32);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarU64(pub u64);
+
+/// Signed integers written as zigzag varints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarI32(pub i32);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarI64(pub i64);
+
+impl<W: WriteStream> Writable<W> for VarU32 {
+    fn write_byte(&self, stream: &mut W) -> Result<(), W::Error> {
+        stream.write_var_u64(self.0 as u64)
+    }
+}
+
+impl<W: WriteStream> Writable<W> for VarU64 {
+    fn write_byte(&self, stream: &mut W) -> Result<(), W::Error> {
+        stream.write_var_u64(self.0)
+    }
+}
+
+impl<W: WriteStream> Writable<W> for VarI32 {
+    fn write_byte(&self, stream: &mut W) -> Result<(), W::Error> {
+        stream.write_var_i64(self.0 as i64)
+    }
+}
+
+impl<W: WriteStream> Writable<W> for VarI64 {
+    fn write_byte(&self, stream: &mut W) -> Result<(), W::Error> {
+        stream.write_var_i64(self.0)
+    }
+}
+
+impl<R: ReadStream> Readable<R> for VarU32 {
+    fn read_byte(stream: &mut R) -> Result<Self, R::Error> {
+        let v = stream.read_var_u64()?;
+        Ok(Self(u32::try_from(v).map_err(|_| VarIntOverflow)?))
+    }
+}
+
+impl<R: ReadStream> Readable<R> for VarU64 {
+    fn read_byte(stream: &mut R) -> Result<Self, R::Error> {
+        Ok(Self(stream.read_var_u64()?))
+    }
+}
+
+impl<R: ReadStream> Readable<R> for VarI32 {
+    fn read_byte(stream: &mut R) -> Result<Self, R::Error> {
+        let v = stream.read_var_i64()?;
+        Ok(Self(i32::try_from(v).map_err(|_| VarIntOverflow)?))
+    }
+}
+
+impl<R: ReadStream> Readable<R> for VarI64 {
+    fn read_byte(stream: &mut R) -> Result<Self, R::Error> {
+        Ok(Self(stream.read_var_i64()?))
+    }
+}
+
+impl<W: WriteBitStream> BitWritable<W> for VarU32 {
+    fn write_bits(&self, stream: &mut W, _bits: usize) -> Result<(), W::Error> {
+        stream.write_var_u64_bits(self.0 as u64)
+    }
+}
+
+impl<W: WriteBitStream> BitWritable<W> for VarU64 {
+    fn write_bits(&self, stream: &mut W, _bits: usize) -> Result<(), W::Error> {
+        stream.write_var_u64_bits(self.0)
+    }
+}
+
+impl<W: WriteBitStream> BitWritable<W> for VarI32 {
+    fn write_bits(&self, stream: &mut W, _bits: usize) -> Result<(), W::Error> {
+        stream.write_var_i64_bits(self.0 as i64)
+    }
+}
+
+impl<W: WriteBitStream> BitWritable<W> for VarI64 {
+    fn write_bits(&self, stream: &mut W, _bits: usize) -> Result<(), W::Error> {
+        stream.write_var_i64_bits(self.0)
+    }
+}
+
+impl<R: ReadBitStream> BitReadable<R> for VarU32 {
+    fn read_bits(stream: &mut R, _bits: usize) -> Result<Self, R::Error> {
+        let v = stream.read_var_u64_bits()?;
+        Ok(Self(u32::try_from(v).map_err(|_| VarIntOverflow)?))
+    }
+}
+
+impl<R: ReadBitStream> BitReadable<R> for VarU64 {
+    fn read_bits(stream: &mut R, _bits: usize) -> Result<Self, R::Error> {
+        Ok(Self(stream.read_var_u64_bits()?))
+    }
+}
+
+impl<R: ReadBitStream> BitReadable<R> for VarI32 {
+    fn read_bits(stream: &mut R, _bits: usize) -> Result<Self, R::Error> {
+        let v = stream.read_var_i64_bits()?;
+        Ok(Self(i32::try_from(v).map_err(|_| VarIntOverflow)?))
+    }
+}
+
+impl<R: ReadBitStream> BitReadable<R> for VarI64 {
+    fn read_bits(stream: &mut R, _bits: usize) -> Result<Self, R::Error> {
+        Ok(Self(stream.read_var_i64_bits()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        io::{
+            bits::{BitReadable, BitWritable, ReadBitStream, WriteBitStream},
+            bytes::{ReadStream, Readable, Writable, WriteStream},
+        },
+        net::io::{GameIoError, InputMemoryStream, OutputMemoryStream},
+    };
+
+    use super::{VarI32, VarI64, VarU32, VarU64};
+
+    fn encode<T: for<'a, 'b> Writable<OutputMemoryStream<'a, 'b, ()>>>(v: T) -> Vec<u8> {
+        let mut buf = vec![];
+        v.write_byte(&mut OutputMemoryStream::new(&mut buf, &mut ()))
+            .unwrap();
+        buf
+    }
+
+    fn decode<T: for<'a, 'b> Readable<InputMemoryStream<'a, 'b, ()>>>(
+        buf: &[u8],
+    ) -> Result<T, GameIoError> {
+        T::read_byte(&mut InputMemoryStream::new(buf, &mut ()))
+    }
+
+    #[test]
+    fn known_encodings() {
+        assert_eq!(encode(VarU64(0)), [0x00]);
+        assert_eq!(encode(VarU64(127)), [0x7F]);
+        assert_eq!(encode(VarU64(128)), [0x80, 0x01]);
+        assert_eq!(encode(VarU64(300)), [0xAC, 0x02]);
+        assert_eq!(encode(VarU64(u64::MAX)).len(), 10);
+        assert_eq!(encode(VarI64(0)), [0x00]);
+        assert_eq!(encode(VarI64(-1)), [0x01]);
+        assert_eq!(encode(VarI64(1)), [0x02]);
+        assert_eq!(encode(VarI64(-64)), [0x7F]);
+        assert_eq!(encode(VarI64(64)), [0x80, 0x01]);
+    }
+
+    #[test]
+    fn boundaries_round_trip() {
+        for v in [0, 1, 127, 128, 16383, 16384, u32::MAX as u64, u64::MAX] {
+            assert_eq!(decode::<VarU64>(&encode(VarU64(v))).unwrap(), VarU64(v));
+        }
+
+        for v in [0, -1, 1, -64, 64, i64::MIN, i64::MAX] {
+            assert_eq!(decode::<VarI64>(&encode(VarI64(v))).unwrap(), VarI64(v));
+        }
+
+        for v in [0, u32::MAX] {
+            assert_eq!(decode::<VarU32>(&encode(VarU32(v))).unwrap(), VarU32(v));
+        }
+
+        for v in [i32::MIN, -1, i32::MAX] {
+            assert_eq!(decode::<VarI32>(&encode(VarI32(v))).unwrap(), VarI32(v));
+        }
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        assert!(matches!(
+            decode::<VarU32>(&encode(VarU64(u32::MAX as u64 + 1))),
+            Err(GameIoError::VarIntOverflow)
+        ));
+        assert!(matches!(
+            decode::<VarI32>(&encode(VarI64(i32::MIN as i64 - 1))),
+            Err(GameIoError::VarIntOverflow)
+        ));
+
+        let mut too_long = vec![0xFF; 9];
+        too_long.push(0x02);
+        assert!(matches!(
+            decode::<VarU64>(&too_long),
+            Err(GameIoError::VarIntOverflow)
+        ));
+        assert!(matches!(
+            decode::<VarU64>(&[0x80]),
+            Err(GameIoError::UnexpectedEof(..))
+        ));
+    }
+
+    #[test]
+    fn bit_encoding_matches_byte_encoding_when_unaligned() {
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        output.write_bool_bits(true, 1).unwrap();
+        VarI32(-300).write_bits(&mut output, 0).unwrap();
+        VarU64(1 << 40).write_bits(&mut output, 0).unwrap();
+        output.write_var_usize(5).unwrap();
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        assert!(input.read_bool_bits(1).unwrap());
+        assert_eq!(VarI32::read_bits(&mut input, 0).unwrap(), VarI32(-300));
+        assert_eq!(VarU64::read_byte(&mut input).unwrap(), VarU64(1 << 40));
+        assert_eq!(input.read_var_usize().unwrap(), 5);
+    }
+}
@@ -0,0 +1,12 @@
+#![feature(array_try_from_fn)]
+
+extern crate self as pha_engine;
+
+pub mod game_object;
+pub mod io;
+pub mod linking_context;
+pub mod net;
+pub mod reflect;
+pub mod utils;
+
+pub use game_object::{GameObject, Replicated};
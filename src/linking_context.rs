@@ -1,13 +1,21 @@
 use std::{
     collections::{HashMap, hash_map::Entry},
+    hash::{Hash, Hasher},
+    mem,
     sync::{Arc, Weak},
+    time::{Duration, Instant},
 };
 
 use crate::{
     GameObject,
-    io::bytes::{ReadStream, Readable, Writable, WriteStream},
+    io::{
+        Reservation,
+        bits::{BitReadable, BitWritable, ReadBitStream, WriteBitStream},
+        bytes::{ErasedReadStream, ReadStream, Readable, Writable, WriteStream},
+    },
     net::io::{GameIoError, InputMemoryStream, OutputMemoryStream},
-    reflect::{Reflect, Ty},
+    reflect::{MemberField, OptionTy, Packing, Reflect, Ty, UserDefinedType, VecTy},
+    utils::{f32_to_fixed, f64_to_fixed, fixed_to_f32, fixed_to_f64},
 };
 
 #[derive(Debug, Default)]
@@ -15,6 +23,60 @@ pub struct LinkingContext {
     next_id: usize,
     id_to_go: HashMap<usize, Arc<dyn GameObject>>,
     go_to_id: HashMap<usize, usize>,
+    pending_refs: HashMap<usize, Vec<PendingRef>>,
+    /// Network id each pending reference waits for, by owner id and path.
+    pending_by_owner: HashMap<usize, HashMap<Vec<RefStep>, usize>>,
+    now: Option<Instant>,
+}
+
+/// A reflected object reference whose target has not been replicated yet.
+#[derive(Debug)]
+struct PendingRef {
+    owner: Weak<dyn GameObject>,
+    owner_id: usize,
+    path: Vec<RefStep>,
+    weak: bool,
+    since: Instant,
+}
+
+/// How to reach a reference field from the start of its owner's data.
+#[derive(Clone, Copy, Debug)]
+enum RefStep {
+    Offset(usize),
+    VecElem(&'static VecTy, usize),
+    OptionSome(&'static OptionTy),
+}
+
+impl PartialEq for RefStep {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (RefStep::Offset(lhs), RefStep::Offset(rhs)) => lhs == rhs,
+            (RefStep::VecElem(_, lhs), RefStep::VecElem(_, rhs)) => lhs == rhs,
+            (RefStep::OptionSome(_), RefStep::OptionSome(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for RefStep {}
+
+impl Hash for RefStep {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            RefStep::Offset(idx) | RefStep::VecElem(_, idx) => idx.hash(state),
+            RefStep::OptionSome(_) => {}
+        }
+    }
+}
+
+/// A reference that was dropped by [`LinkingContext::take_expired_refs`].
+#[derive(Clone, Debug)]
+pub struct UnresolvedRef {
+    /// Local id of the object holding the reference, if it is still alive.
+    pub owner: Option<usize>,
+    pub network_id: usize,
+    pub age: Duration,
 }
 
 impl LinkingContext {
@@ -42,13 +104,155 @@ impl LinkingContext {
 
     pub fn insert_game_object(&mut self, go: Arc<dyn GameObject>, id: usize) {
         self.go_to_id.insert(go.id(), id);
-        self.id_to_go.insert(id, go);
+        self.id_to_go.insert(id, go.clone());
+        self.resolve_pending_refs(&go, id);
     }
 
-    pub fn remove_game_object(&mut self, go: usize) {
-        let id = self.go_to_id.remove(&go).unwrap();
+    /// Returns whether `go` was registered.
+    pub fn remove_game_object(&mut self, go: usize) -> bool {
+        let Some(id) = self.go_to_id.remove(&go) else {
+            return false;
+        };
         self.id_to_go.remove(&id);
+
+        for network_id in self
+            .pending_by_owner
+            .remove(&go)
+            .into_iter()
+            .flat_map(|paths| paths.into_values())
+        {
+            if let Entry::Occupied(mut refs) = self.pending_refs.entry(network_id) {
+                refs.get_mut().retain(|pending| pending.owner_id != go);
+                if refs.get().is_empty() {
+                    refs.remove();
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Sets the time references read from now on start waiting at, so that
+    /// [`Self::take_expired_refs`] can be driven by the same clock. Defaults to the wall clock.
+    pub fn set_now(&mut self, now: Instant) {
+        self.now = Some(now);
+    }
+
+    pub fn unresolved_ref_count(&self) -> usize {
+        self.pending_refs.values().map(Vec::len).sum()
+    }
+
+    /// Stops waiting for references that have been unresolved for at least `timeout` and
+    /// returns them for diagnostics. The reference fields themselves stay `None`.
+    pub fn take_expired_refs(&mut self, now: Instant, timeout: Duration) -> Vec<UnresolvedRef> {
+        let mut expired = vec![];
+
+        self.pending_refs.retain(|&network_id, refs| {
+            refs.retain(|pending| {
+                let age = now.saturating_duration_since(pending.since);
+                if age < timeout {
+                    return true;
+                }
+
+                unindex_pending_ref(&mut self.pending_by_owner, pending);
+
+                expired.push(UnresolvedRef {
+                    owner: pending.owner.upgrade().map(|owner| owner.id()),
+                    network_id,
+                    age,
+                });
+                false
+            });
+            !refs.is_empty()
+        });
+
+        expired
+    }
+
+    fn defer_ref(&mut self, id: usize, pending: PendingRef) {
+        self.pending_by_owner
+            .entry(pending.owner_id)
+            .or_default()
+            .insert(pending.path.clone(), id);
+        self.pending_refs.entry(id).or_default().push(pending);
     }
+
+    fn cancel_pending_ref(&mut self, owner_id: usize, path: &[RefStep]) {
+        let Entry::Occupied(mut paths) = self.pending_by_owner.entry(owner_id) else {
+            return;
+        };
+        let Some(network_id) = paths.get_mut().remove(path) else {
+            return;
+        };
+        if paths.get().is_empty() {
+            paths.remove();
+        }
+
+        if let Entry::Occupied(mut refs) = self.pending_refs.entry(network_id) {
+            refs.get_mut()
+                .retain(|pending| !(pending.owner_id == owner_id && pending.path == path));
+            if refs.get().is_empty() {
+                refs.remove();
+            }
+        }
+    }
+
+    fn resolve_pending_refs(&mut self, go: &Arc<dyn GameObject>, id: usize) {
+        for pending in self.pending_refs.remove(&id).unwrap_or_default() {
+            unindex_pending_ref(&mut self.pending_by_owner, &pending);
+
+            let Some(owner) = pending.owner.upgrade() else {
+                continue;
+            };
+
+            let _ = owner.with_data_mut(&mut |data| {
+                unsafe {
+                    if let Some(field) = resolve_path(data, &pending.path) {
+                        set_object_ref(field, Some(go.clone()), pending.weak);
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+}
+
+fn unindex_pending_ref(
+    pending_by_owner: &mut HashMap<usize, HashMap<Vec<RefStep>, usize>>,
+    pending: &PendingRef,
+) {
+    if let Entry::Occupied(mut paths) = pending_by_owner.entry(pending.owner_id) {
+        paths.get_mut().remove(&pending.path);
+        if paths.get().is_empty() {
+            paths.remove();
+        }
+    }
+}
+
+/// Follows `path` from `data`, returning `None` if the container it went through has since
+/// shrunk or been emptied.
+unsafe fn resolve_path(mut data: *mut u8, path: &[RefStep]) -> Option<*mut u8> {
+    unsafe {
+        for step in path {
+            data = match *step {
+                RefStep::Offset(offset) => data.add(offset),
+                RefStep::VecElem(info, idx) if idx < (info.len)(data) => {
+                    (info.as_mut_ptr)(data).add(idx * info.elem.size())
+                }
+                RefStep::VecElem(..) => return None,
+                RefStep::OptionSome(info) => {
+                    let value = (info.get_mut)(data);
+                    if value.is_null() {
+                        return None;
+                    }
+
+                    value
+                }
+            };
+        }
+    }
+
+    Some(data)
 }
 
 impl Writable<OutputMemoryStream<'_, '_, LinkingContext>> for Option<Weak<dyn GameObject>> {
@@ -69,7 +273,7 @@ impl Writable<OutputMemoryStream<'_, '_, LinkingContext>> for Arc<dyn GameObject
             .ctx
             .get_network_id(self, false)
             .ok_or(GameIoError::UnregisteredGameObject(0))?;
-        stream.write_usize(id)?;
+        stream.write_var_usize(id)?;
 
         Ok(())
     }
@@ -79,7 +283,7 @@ impl Readable<InputMemoryStream<'_, '_, LinkingContext>> for Arc<dyn GameObject>
     fn read_byte(
         stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
     ) -> Result<Self, GameIoError> {
-        let id = stream.read_usize()?;
+        let id = stream.read_var_usize()?;
         let go = stream
             .ctx
             .get_game_object(id)
@@ -97,7 +301,7 @@ impl Readable<InputMemoryStream<'_, '_, LinkingContext>> for Option<Weak<dyn Gam
             return Ok(None);
         }
 
-        let id = stream.read_usize()?;
+        let id = stream.read_var_usize()?;
         let go = stream
             .ctx
             .get_game_object(id)
@@ -107,56 +311,851 @@ impl Readable<InputMemoryStream<'_, '_, LinkingContext>> for Option<Weak<dyn Gam
     }
 }
 
-impl<T> Writable<OutputMemoryStream<'_, '_, LinkingContext>> for T
-where
-    T: Reflect,
-{
-    fn write_byte(
-        &self,
+/// Whether values are written with the byte-level `Writable` encoding or bit-packed according to
+/// each field's [`Packing`].
+#[derive(Clone, Copy, Debug)]
+enum Encoding {
+    Bytes,
+    Bits(Packing),
+}
+
+impl Encoding {
+    fn field(self, field: &MemberField) -> Self {
+        match self {
+            Encoding::Bytes => Encoding::Bytes,
+            Encoding::Bits(_) => Encoding::Bits(field.packing),
+        }
+    }
+
+    fn write_bool(
+        self,
         stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+        v: bool,
     ) -> Result<(), GameIoError> {
-        unsafe {
-            let this = self as *const T as *const u8;
+        match self {
+            Encoding::Bytes => stream.write_bool(v),
+            Encoding::Bits(_) => stream.write_bool_bits(v, 1),
+        }
+    }
 
-            for field in self.reflect().fields {
-                match field.ty {
-                    Ty::Int => u32::write_byte(&*(this.add(field.offset) as *const u32), stream)?,
-                    Ty::String => {
-                        String::write_byte(&*(this.add(field.offset) as *const String), stream)?
+    fn read_bool(
+        self,
+        stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ) -> Result<bool, GameIoError> {
+        match self {
+            Encoding::Bytes => stream.read_bool(),
+            Encoding::Bits(_) => stream.read_bool_bits(1),
+        }
+    }
+
+    /// Fewest bits a value of type `ty` takes on the wire.
+    fn min_bits(self, ty: Ty) -> usize {
+        match (self, ty.size()) {
+            (_, 0) => 0,
+            (Encoding::Bytes, _) => 8,
+            (Encoding::Bits(_), _) => 1,
+        }
+    }
+}
+
+unsafe fn write_typed<T>(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    data: *const u8,
+) -> Result<(), GameIoError>
+where
+    T: for<'ctx, 'buffer> Writable<OutputMemoryStream<'ctx, 'buffer, LinkingContext>>,
+{
+    unsafe { (*(data as *const T)).write_byte(stream) }
+}
+
+unsafe fn read_typed<T>(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    data: *mut u8,
+) -> Result<(), GameIoError>
+where
+    T: for<'ctx, 'buffer> Readable<InputMemoryStream<'ctx, 'buffer, LinkingContext>>,
+{
+    unsafe { *(data as *mut T) = T::read_byte(stream)? };
+
+    Ok(())
+}
+
+unsafe fn write_plain(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    ty: Ty,
+    data: *const u8,
+) -> Result<(), GameIoError> {
+    unsafe {
+        match ty {
+            Ty::Bool => write_typed::<bool>(stream, data),
+            Ty::U8 => write_typed::<u8>(stream, data),
+            Ty::U16 => write_typed::<u16>(stream, data),
+            Ty::U32 => write_typed::<u32>(stream, data),
+            Ty::U64 => write_typed::<u64>(stream, data),
+            Ty::I8 => write_typed::<i8>(stream, data),
+            Ty::I16 => write_typed::<i16>(stream, data),
+            Ty::I32 => write_typed::<i32>(stream, data),
+            Ty::I64 => write_typed::<i64>(stream, data),
+            Ty::F32 => write_typed::<f32>(stream, data),
+            Ty::F64 => write_typed::<f64>(stream, data),
+            Ty::String => write_typed::<String>(stream, data),
+            Ty::Vec2 => write_typed::<glam::Vec2>(stream, data),
+            Ty::Vec3 => write_typed::<glam::Vec3>(stream, data),
+            Ty::Vec4 => write_typed::<glam::Vec4>(stream, data),
+            Ty::Mat2 => write_typed::<glam::Mat2>(stream, data),
+            Ty::Mat3 => write_typed::<glam::Mat3>(stream, data),
+            Ty::Mat4 => write_typed::<glam::Mat4>(stream, data),
+            Ty::Quat => write_typed::<glam::Quat>(stream, data),
+            _ => unreachable!("{ty:?} is not a plain value"),
+        }
+    }
+}
+
+unsafe fn read_plain(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ty: Ty,
+    data: *mut u8,
+) -> Result<(), GameIoError> {
+    unsafe {
+        match ty {
+            Ty::Bool => read_typed::<bool>(stream, data),
+            Ty::U8 => read_typed::<u8>(stream, data),
+            Ty::U16 => read_typed::<u16>(stream, data),
+            Ty::U32 => read_typed::<u32>(stream, data),
+            Ty::U64 => read_typed::<u64>(stream, data),
+            Ty::I8 => read_typed::<i8>(stream, data),
+            Ty::I16 => read_typed::<i16>(stream, data),
+            Ty::I32 => read_typed::<i32>(stream, data),
+            Ty::I64 => read_typed::<i64>(stream, data),
+            Ty::F32 => read_typed::<f32>(stream, data),
+            Ty::F64 => read_typed::<f64>(stream, data),
+            Ty::String => read_typed::<String>(stream, data),
+            Ty::Vec2 => read_typed::<glam::Vec2>(stream, data),
+            Ty::Vec3 => read_typed::<glam::Vec3>(stream, data),
+            Ty::Vec4 => read_typed::<glam::Vec4>(stream, data),
+            Ty::Mat2 => read_typed::<glam::Mat2>(stream, data),
+            Ty::Mat3 => read_typed::<glam::Mat3>(stream, data),
+            Ty::Mat4 => read_typed::<glam::Mat4>(stream, data),
+            Ty::Quat => read_typed::<glam::Quat>(stream, data),
+            _ => unreachable!("{ty:?} is not a plain value"),
+        }
+    }
+}
+
+fn int_bits(ty: Ty, packing: Packing) -> usize {
+    match packing {
+        Packing::Bits(bits) => bits as usize,
+        _ => ty.size() * 8,
+    }
+}
+
+fn write_unsigned(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    v: u64,
+    bits: usize,
+) -> Result<(), GameIoError> {
+    stream.write_u64_bits(v.min(u64::MAX >> (64 - bits)), bits)
+}
+
+fn write_signed(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    v: i64,
+    bits: usize,
+) -> Result<(), GameIoError> {
+    let v = v.clamp(i64::MIN >> (64 - bits), i64::MAX >> (64 - bits));
+    stream.write_u64_bits(v as u64 & (u64::MAX >> (64 - bits)), bits)
+}
+
+fn read_unsigned(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    bits: usize,
+) -> Result<u64, GameIoError> {
+    stream.read_u64_bits(bits)
+}
+
+fn read_signed(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    bits: usize,
+) -> Result<i64, GameIoError> {
+    let v = stream.read_u64_bits(bits)?;
+    Ok(((v << (64 - bits)) as i64) >> (64 - bits))
+}
+
+fn write_floats(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    values: &[f32],
+    packing: Packing,
+) -> Result<(), GameIoError> {
+    for &v in values {
+        match packing {
+            Packing::Fixed {
+                min,
+                max,
+                precision,
+                bits,
+            } => stream.write_u32_bits(
+                f32_to_fixed(v.clamp(min, max), min, precision),
+                bits as usize,
+            )?,
+            _ => stream.write_f32_bits(v, 32)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn read_floats(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    values: &mut [f32],
+    packing: Packing,
+) -> Result<(), GameIoError> {
+    for v in values {
+        *v = match packing {
+            Packing::Fixed {
+                min,
+                precision,
+                bits,
+                ..
+            } => fixed_to_f32(stream.read_u32_bits(bits as usize)?, min, precision),
+            _ => stream.read_f32_bits(32)?,
+        };
+    }
+
+    Ok(())
+}
+
+unsafe fn write_packed(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    ty: Ty,
+    data: *const u8,
+    packing: Packing,
+) -> Result<(), GameIoError> {
+    let bits = int_bits(ty, packing);
+
+    unsafe {
+        match ty {
+            Ty::Bool => stream.write_bool_bits(*(data as *const bool), 1),
+            Ty::U8 => write_unsigned(stream, *data as u64, bits),
+            Ty::U16 => write_unsigned(stream, *(data as *const u16) as u64, bits),
+            Ty::U32 => write_unsigned(stream, *(data as *const u32) as u64, bits),
+            Ty::U64 => write_unsigned(stream, *(data as *const u64), bits),
+            Ty::I8 => write_signed(stream, *(data as *const i8) as i64, bits),
+            Ty::I16 => write_signed(stream, *(data as *const i16) as i64, bits),
+            Ty::I32 => write_signed(stream, *(data as *const i32) as i64, bits),
+            Ty::I64 => write_signed(stream, *(data as *const i64), bits),
+            Ty::F32 => write_floats(stream, &[*(data as *const f32)], packing),
+            Ty::F64 => {
+                let v = *(data as *const f64);
+                match packing {
+                    Packing::Fixed {
+                        min,
+                        max,
+                        precision,
+                        bits,
+                    } => {
+                        let (min, max, precision) = (min as f64, max as f64, precision as f64);
+                        stream.write_u64_bits(
+                            f64_to_fixed(v.clamp(min, max), min, precision),
+                            bits as usize,
+                        )
                     }
-                    Ty::Float => f32::write_byte(&*(this.add(field.offset) as *const f32), stream)?,
+                    _ => stream.write_f64_bits(v, 64),
                 }
             }
+            Ty::String => (*(data as *const String)).write_bits(stream, 8),
+            Ty::Vec2 => write_floats(stream, &(*(data as *const glam::Vec2)).to_array(), packing),
+            Ty::Vec3 => write_floats(stream, &(*(data as *const glam::Vec3)).to_array(), packing),
+            Ty::Vec4 => write_floats(stream, &(*(data as *const glam::Vec4)).to_array(), packing),
+            Ty::Mat2 => write_floats(
+                stream,
+                &(*(data as *const glam::Mat2)).to_cols_array(),
+                packing,
+            ),
+            Ty::Mat3 => write_floats(
+                stream,
+                &(*(data as *const glam::Mat3)).to_cols_array(),
+                packing,
+            ),
+            Ty::Mat4 => write_floats(
+                stream,
+                &(*(data as *const glam::Mat4)).to_cols_array(),
+                packing,
+            ),
+            Ty::Quat => write_floats(stream, &(*(data as *const glam::Quat)).to_array(), packing),
+            _ => unreachable!("{ty:?} is not a plain value"),
         }
+    }
+}
 
-        Ok(())
+unsafe fn read_packed(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ty: Ty,
+    data: *mut u8,
+    packing: Packing,
+) -> Result<(), GameIoError> {
+    let bits = int_bits(ty, packing);
+
+    unsafe {
+        match ty {
+            Ty::Bool => *(data as *mut bool) = stream.read_bool_bits(1)?,
+            Ty::U8 => *data = read_unsigned(stream, bits)? as u8,
+            Ty::U16 => *(data as *mut u16) = read_unsigned(stream, bits)? as u16,
+            Ty::U32 => *(data as *mut u32) = read_unsigned(stream, bits)? as u32,
+            Ty::U64 => *(data as *mut u64) = read_unsigned(stream, bits)?,
+            Ty::I8 => *(data as *mut i8) = read_signed(stream, bits)? as i8,
+            Ty::I16 => *(data as *mut i16) = read_signed(stream, bits)? as i16,
+            Ty::I32 => *(data as *mut i32) = read_signed(stream, bits)? as i32,
+            Ty::I64 => *(data as *mut i64) = read_signed(stream, bits)?,
+            Ty::F32 => {
+                let mut v = [0.0];
+                read_floats(stream, &mut v, packing)?;
+                *(data as *mut f32) = v[0];
+            }
+            Ty::F64 => {
+                *(data as *mut f64) = match packing {
+                    Packing::Fixed {
+                        min,
+                        precision,
+                        bits,
+                        ..
+                    } => fixed_to_f64(
+                        stream.read_u64_bits(bits as usize)?,
+                        min as f64,
+                        precision as f64,
+                    ),
+                    _ => stream.read_f64_bits(64)?,
+                }
+            }
+            Ty::String => *(data as *mut String) = String::read_bits(stream, 8)?,
+            Ty::Vec2 => {
+                let mut v = [0.0; 2];
+                read_floats(stream, &mut v, packing)?;
+                *(data as *mut glam::Vec2) = glam::Vec2::from_array(v);
+            }
+            Ty::Vec3 => {
+                let mut v = [0.0; 3];
+                read_floats(stream, &mut v, packing)?;
+                *(data as *mut glam::Vec3) = glam::Vec3::from_array(v);
+            }
+            Ty::Vec4 => {
+                let mut v = [0.0; 4];
+                read_floats(stream, &mut v, packing)?;
+                *(data as *mut glam::Vec4) = glam::Vec4::from_array(v);
+            }
+            Ty::Mat2 => {
+                let mut v = [0.0; 4];
+                read_floats(stream, &mut v, packing)?;
+                *(data as *mut glam::Mat2) = glam::Mat2::from_cols_array(&v);
+            }
+            Ty::Mat3 => {
+                let mut v = [0.0; 9];
+                read_floats(stream, &mut v, packing)?;
+                *(data as *mut glam::Mat3) = glam::Mat3::from_cols_array(&v);
+            }
+            Ty::Mat4 => {
+                let mut v = [0.0; 16];
+                read_floats(stream, &mut v, packing)?;
+                *(data as *mut glam::Mat4) = glam::Mat4::from_cols_array(&v);
+            }
+            Ty::Quat => {
+                let mut v = [0.0; 4];
+                read_floats(stream, &mut v, packing)?;
+                *(data as *mut glam::Quat) = glam::Quat::from_array(v);
+            }
+            _ => unreachable!("{ty:?} is not a plain value"),
+        }
+    }
+
+    Ok(())
+}
+
+fn write_object_ref(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    go: Option<Arc<dyn GameObject>>,
+    enc: Encoding,
+) -> Result<(), GameIoError> {
+    enc.write_bool(stream, go.is_some())?;
+
+    match go {
+        Some(go) => go.write_byte(stream),
+        None => Ok(()),
+    }
+}
+
+unsafe fn write_value(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    ty: Ty,
+    data: *const u8,
+    enc: Encoding,
+) -> Result<(), GameIoError> {
+    unsafe {
+        match ty {
+            Ty::ObjectRef => {
+                let go = (*(data as *const Option<Arc<dyn GameObject>>)).clone();
+                write_object_ref(stream, go, enc)
+            }
+            Ty::WeakObjectRef => {
+                let go = (*(data as *const Option<Weak<dyn GameObject>>))
+                    .as_ref()
+                    .and_then(Weak::upgrade);
+                write_object_ref(stream, go, enc)
+            }
+            Ty::Vec(info) => {
+                let len = (info.len)(data);
+                let elems = (info.as_ptr)(data);
+                stream.write_var_usize(len)?;
+
+                for idx in 0..len {
+                    write_value(stream, info.elem, elems.add(idx * info.elem.size()), enc)?;
+                }
+
+                Ok(())
+            }
+            Ty::Option(info) => {
+                let value = (info.get)(data);
+                enc.write_bool(stream, !value.is_null())?;
+
+                if value.is_null() {
+                    Ok(())
+                } else {
+                    write_value(stream, info.elem, value, enc)
+                }
+            }
+            Ty::Array(info) => {
+                for idx in 0..info.len {
+                    write_value(stream, info.elem, data.add(idx * info.elem.size()), enc)?;
+                }
+
+                Ok(())
+            }
+            Ty::Struct(info) => write_fields(stream, info.ty, data, info.ty.all_fields_mask(), enc),
+            _ => match enc {
+                Encoding::Bytes => write_plain(stream, ty, data),
+                Encoding::Bits(packing) => write_packed(stream, ty, data, packing),
+            },
+        }
+    }
+}
+
+/// The object being read and the path to the current value, used to patch references to
+/// objects that arrive later.
+struct RefOwner<'a> {
+    go: &'a Weak<dyn GameObject>,
+    id: usize,
+    path: Vec<RefStep>,
+}
+
+unsafe fn set_object_ref(data: *mut u8, go: Option<Arc<dyn GameObject>>, weak: bool) {
+    unsafe {
+        if weak {
+            *(data as *mut Option<Weak<dyn GameObject>>) = go.as_ref().map(Arc::downgrade);
+        } else {
+            *(data as *mut Option<Arc<dyn GameObject>>) = go;
+        }
+    }
+}
+
+unsafe fn read_object_ref(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    data: *mut u8,
+    owner: Option<&mut RefOwner<'_>>,
+    weak: bool,
+    enc: Encoding,
+) -> Result<(), GameIoError> {
+    let id = if enc.read_bool(stream)? {
+        Some(stream.read_var_usize()?)
+    } else {
+        None
+    };
+    let go = id.and_then(|id| stream.ctx.get_game_object(id));
+
+    if let Some(owner) = owner {
+        stream.ctx.cancel_pending_ref(owner.id, &owner.path);
+
+        if let (Some(id), None) = (id, &go) {
+            stream.ctx.defer_ref(
+                id,
+                PendingRef {
+                    owner: owner.go.clone(),
+                    owner_id: owner.id,
+                    path: owner.path.clone(),
+                    weak,
+                    since: stream.ctx.now.unwrap_or_else(Instant::now),
+                },
+            );
+        }
+    }
+
+    unsafe { set_object_ref(data, go, weak) };
+
+    Ok(())
+}
+
+unsafe fn read_nested(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ty: Ty,
+    data: *mut u8,
+    mut owner: Option<&mut RefOwner<'_>>,
+    step: RefStep,
+    enc: Encoding,
+) -> Result<(), GameIoError> {
+    if let Some(owner) = owner.as_deref_mut() {
+        owner.path.push(step);
+    }
+
+    let result = unsafe { read_value(stream, ty, data, owner.as_deref_mut(), enc) };
+
+    if let Some(owner) = owner {
+        owner.path.pop();
+    }
+
+    result
+}
+
+unsafe fn read_value(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ty: Ty,
+    data: *mut u8,
+    mut owner: Option<&mut RefOwner<'_>>,
+    enc: Encoding,
+) -> Result<(), GameIoError> {
+    unsafe {
+        match ty {
+            Ty::ObjectRef => read_object_ref(stream, data, owner, false, enc),
+            Ty::WeakObjectRef => read_object_ref(stream, data, owner, true, enc),
+            Ty::Vec(info) => {
+                let len = stream.read_var_usize()?;
+                stream.reserve(Reservation::Collection {
+                    len,
+                    size: info.elem.size(),
+                    min_bits: enc.min_bits(info.elem),
+                })?;
+                (info.clear)(data);
+
+                for idx in 0..len {
+                    let elem = (info.push_default)(data);
+                    let step = RefStep::VecElem(info, idx);
+                    read_nested(stream, info.elem, elem, owner.as_deref_mut(), step, enc)?;
+                }
+
+                Ok(())
+            }
+            Ty::Option(info) => {
+                if enc.read_bool(stream)? {
+                    let value = (info.insert_default)(data);
+                    let step = RefStep::OptionSome(info);
+                    read_nested(stream, info.elem, value, owner, step, enc)
+                } else {
+                    (info.clear)(data);
+                    Ok(())
+                }
+            }
+            Ty::Array(info) => {
+                for idx in 0..info.len {
+                    let offset = idx * info.elem.size();
+                    let step = RefStep::Offset(offset);
+                    let elem = data.add(offset);
+                    read_nested(stream, info.elem, elem, owner.as_deref_mut(), step, enc)?;
+                }
+
+                Ok(())
+            }
+            Ty::Struct(info) => {
+                let dirty_state = info.ty.all_fields_mask();
+                read_fields(stream, info.ty, data, dirty_state, owner, enc)
+            }
+            _ => match enc {
+                Encoding::Bytes => read_plain(stream, ty, data),
+                Encoding::Bits(packing) => read_packed(stream, ty, data, packing),
+            },
+        }
+    }
+}
+
+unsafe fn write_fields(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    ty: &UserDefinedType,
+    data: *const u8,
+    dirty_state: u64,
+    enc: Encoding,
+) -> Result<(), GameIoError> {
+    for (_, field) in ty
+        .fields
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| dirty_state & (1 << idx) != 0)
+    {
+        unsafe { write_value(stream, field.ty, data.add(field.offset), enc.field(field))? };
+    }
+
+    Ok(())
+}
+
+unsafe fn read_fields(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ty: &UserDefinedType,
+    data: *mut u8,
+    dirty_state: u64,
+    mut owner: Option<&mut RefOwner<'_>>,
+    enc: Encoding,
+) -> Result<(), GameIoError> {
+    for (_, field) in ty
+        .fields
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| dirty_state & (1 << idx) != 0)
+    {
+        let step = RefStep::Offset(field.offset);
+        let value = unsafe { data.add(field.offset) };
+        let enc = enc.field(field);
+        unsafe { read_nested(stream, field.ty, value, owner.as_deref_mut(), step, enc)? };
+    }
+
+    Ok(())
+}
+
+/// # Safety
+///
+/// `data` must point to a live value whose layout is described by `ty`.
+pub unsafe fn write_reflected(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    ty: &UserDefinedType,
+    data: *const u8,
+) -> Result<(), GameIoError> {
+    unsafe { write_reflected_fields(stream, ty, data, ty.all_fields_mask()) }
+}
+
+/// Writes only the fields whose bit is set in `dirty_state`.
+///
+/// # Safety
+///
+/// `data` must point to a live value whose layout is described by `ty`.
+pub unsafe fn write_reflected_fields(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    ty: &UserDefinedType,
+    data: *const u8,
+    dirty_state: u64,
+) -> Result<(), GameIoError> {
+    ty.validate()?;
+
+    unsafe { write_fields(stream, ty, data, dirty_state, Encoding::Bytes) }
+}
+
+/// # Safety
+///
+/// `data` must point to a live, initialized value whose layout is described by `ty`.
+pub unsafe fn read_reflected(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ty: &UserDefinedType,
+    data: *mut u8,
+) -> Result<(), GameIoError> {
+    unsafe { read_reflected_fields(stream, ty, data, ty.all_fields_mask()) }
+}
+
+/// Reads only the fields whose bit is set in `dirty_state`, leaving the rest untouched.
+///
+/// # Safety
+///
+/// `data` must point to a live, initialized value whose layout is described by `ty`.
+pub unsafe fn read_reflected_fields(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ty: &UserDefinedType,
+    data: *mut u8,
+    dirty_state: u64,
+) -> Result<(), GameIoError> {
+    ty.validate()?;
+
+    unsafe { read_fields(stream, ty, data, dirty_state, None, Encoding::Bytes) }
+}
+
+/// Writes the reflected fields of `go` whose bit is set in `dirty_state`.
+pub fn write_game_object(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    go: &Arc<dyn GameObject>,
+    dirty_state: u64,
+) -> Result<(), GameIoError> {
+    let ty = go.type_info();
+    ty.validate()?;
+
+    go.with_data(&mut |data| unsafe {
+        write_fields(stream, ty, data, dirty_state, Encoding::Bytes)
+    })
+}
+
+/// Reads the reflected fields of `go` whose bit is set in `dirty_state`. References to objects
+/// that are not registered yet are left `None` and patched once the target is inserted into the
+/// [`LinkingContext`], see [`LinkingContext::take_expired_refs`] for the ones that never are.
+pub fn read_game_object(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    go: &Arc<dyn GameObject>,
+    dirty_state: u64,
+) -> Result<(), GameIoError> {
+    let ty = go.type_info();
+    ty.validate()?;
+
+    let owner = Arc::downgrade(go);
+    go.with_data_mut(&mut |data| unsafe {
+        let mut owner = RefOwner {
+            go: &owner,
+            id: go.id(),
+            path: vec![],
+        };
+        read_fields(
+            stream,
+            ty,
+            data,
+            dirty_state,
+            Some(&mut owner),
+            Encoding::Bytes,
+        )
+    })
+}
+
+/// Bit-packed variant of [`write_reflected_fields`], encoding each field according to its
+/// [`Packing`].
+///
+/// # Safety
+///
+/// `data` must point to a live value whose layout is described by `ty`.
+pub unsafe fn write_reflected_packed(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    ty: &UserDefinedType,
+    data: *const u8,
+    dirty_state: u64,
+) -> Result<(), GameIoError> {
+    ty.validate()?;
+
+    unsafe { write_fields(stream, ty, data, dirty_state, Encoding::Bits(Packing::Full)) }
+}
+
+/// Bit-packed variant of [`read_reflected_fields`].
+///
+/// # Safety
+///
+/// `data` must point to a live, initialized value whose layout is described by `ty`.
+pub unsafe fn read_reflected_packed(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ty: &UserDefinedType,
+    data: *mut u8,
+    dirty_state: u64,
+) -> Result<(), GameIoError> {
+    ty.validate()?;
+
+    let enc = Encoding::Bits(Packing::Full);
+    unsafe { read_fields(stream, ty, data, dirty_state, None, enc) }
+}
+
+/// Bit-packed variant of [`write_game_object`].
+pub fn write_game_object_packed(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    go: &Arc<dyn GameObject>,
+    dirty_state: u64,
+) -> Result<(), GameIoError> {
+    let ty = go.type_info();
+    ty.validate()?;
+
+    let enc = Encoding::Bits(Packing::Full);
+    go.with_data(&mut |data| unsafe { write_fields(stream, ty, data, dirty_state, enc) })
+}
+
+/// Bit-packed variant of [`read_game_object`].
+pub fn read_game_object_packed(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    go: &Arc<dyn GameObject>,
+    dirty_state: u64,
+) -> Result<(), GameIoError> {
+    let ty = go.type_info();
+    ty.validate()?;
+
+    let owner = Arc::downgrade(go);
+    let enc = Encoding::Bits(Packing::Full);
+    go.with_data_mut(&mut |data| unsafe {
+        let mut owner = RefOwner {
+            go: &owner,
+            id: go.id(),
+            path: vec![],
+        };
+        read_fields(stream, ty, data, dirty_state, Some(&mut owner), enc)
+    })
+}
+
+impl<T> Writable<OutputMemoryStream<'_, '_, LinkingContext>> for T
+where
+    T: Reflect + 'static,
+{
+    fn write_byte(
+        &self,
+        stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    ) -> Result<(), GameIoError> {
+        let ty = self.reflect();
+        ty.validate_for::<T>()?;
+
+        let data = self as *const T as *const u8;
+        unsafe { write_fields(stream, ty, data, ty.all_fields_mask(), Encoding::Bytes) }
     }
 }
 
 impl<T> Readable<InputMemoryStream<'_, '_, LinkingContext>> for T
 where
-    T: Reflect + Default,
+    T: Reflect + Default + 'static,
 {
     fn read_byte(
         stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
     ) -> Result<Self, GameIoError> {
+        let mut ret = T::default();
+        let ty = ret.reflect();
+        ty.validate_for::<T>()?;
+
+        let data = &mut ret as *mut T as *mut u8;
         unsafe {
-            let mut ret = T::default();
-            let this = &mut ret as *mut T as *mut u8;
+            read_fields(
+                stream,
+                ty,
+                data,
+                ty.all_fields_mask(),
+                None,
+                Encoding::Bytes,
+            )?
+        };
 
-            for field in ret.reflect().fields {
-                match field.ty {
-                    Ty::Int => {
-                        *(this.add(field.offset) as *mut u32) = u32::read_byte(stream)?;
-                    }
-                    Ty::String => {
-                        *(this.add(field.offset) as *mut String) = String::read_byte(stream)?
-                    }
-                    Ty::Float => *(this.add(field.offset) as *mut f32) = f32::read_byte(stream)?,
-                }
-            }
+        Ok(ret)
+    }
+}
 
-            Ok(ret)
-        }
+impl<T> BitWritable<OutputMemoryStream<'_, '_, LinkingContext>> for T
+where
+    T: Reflect + 'static,
+{
+    fn write_bits(
+        &self,
+        stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+        _bits: usize,
+    ) -> Result<(), GameIoError> {
+        let ty = self.reflect();
+        ty.validate_for::<T>()?;
+
+        let data = self as *const T as *const u8;
+        let enc = Encoding::Bits(Packing::Full);
+        unsafe { write_fields(stream, ty, data, ty.all_fields_mask(), enc) }
+    }
+}
+
+impl<T> BitReadable<InputMemoryStream<'_, '_, LinkingContext>> for T
+where
+    T: Reflect + Default + 'static,
+{
+    fn read_bits(
+        stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+        _bits: usize,
+    ) -> Result<Self, GameIoError> {
+        let mut ret = T::default();
+        let ty = ret.reflect();
+        ty.validate_for::<T>()?;
+
+        let data = &mut ret as *mut T as *mut u8;
+        let enc = Encoding::Bits(Packing::Full);
+        unsafe { read_fields(stream, ty, data, ty.all_fields_mask(), None, enc)? };
+
+        Ok(ret)
     }
 }
@@ -1,4 +1,7 @@
-use crate::io::bits::{ErasedReadBitStream, ErasedWriteBitStream};
+use crate::io::{
+    ByteOrder, Reservation,
+    bits::{ErasedReadBitStream, ErasedWriteBitStream},
+};
 
 use super::{GameIoError, InputMemoryStream, OutputMemoryStream};
 
@@ -6,17 +9,24 @@ impl<'ctx, 'buffer, T> ErasedWriteBitStream for OutputMemoryStream<'ctx, 'buffer
     type Error = GameIoError;
 
     fn write_byte_bits(&mut self, v: u8, bits: usize) -> Result<(), Self::Error> {
-        let new_head = self.head + bits;
-        let byte_diff = new_head >> 3 - self.buffer.len();
+        debug_assert!(bits <= 8, "cannot write {bits} bits from a byte");
+
+        if bits == 0 {
+            return Ok(());
+        }
 
-        self.buffer
-            .try_reserve(byte_diff)
-            .map_err(|_| GameIoError::Oom)?;
+        let new_head = self.head + bits;
+        let new_len = new_head.div_ceil(8);
 
-        if byte_diff > 0 {
-            self.buffer.push(0);
+        if new_len > self.buffer.len() {
+            self.buffer
+                .try_reserve(new_len - self.buffer.len())
+                .map_err(|_| GameIoError::Oom)?;
+            self.buffer.resize(new_len, 0);
         }
 
+        // Bits past `bits` would otherwise leak into the padding of the last byte.
+        let v = v & (0xFF >> (8 - bits));
         let byte_offset = self.head >> 3;
         let bit_offset = self.head & 0x7;
         let mask = !(0xFFu8 << bit_offset);
@@ -33,6 +43,12 @@ impl<'ctx, 'buffer, T> ErasedWriteBitStream for OutputMemoryStream<'ctx, 'buffer
     }
 
     fn write_any_bits(&mut self, v: &[u8], mut bits: usize) -> Result<(), Self::Error> {
+        assert!(
+            bits <= v.len() * 8,
+            "cannot write {bits} bits from {} bytes",
+            v.len()
+        );
+
         let mut idx = 0;
 
         while bits > 8 {
@@ -47,12 +63,23 @@ impl<'ctx, 'buffer, T> ErasedWriteBitStream for OutputMemoryStream<'ctx, 'buffer
 
         Ok(())
     }
+
+    fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
 }
 
 impl<'ctx, 'buffer, T> ErasedReadBitStream for InputMemoryStream<'ctx, 'buffer, T> {
     type Error = GameIoError;
 
     fn read_byte_bit(&mut self, bits: usize) -> Result<u8, Self::Error> {
+        debug_assert!(bits <= 8, "cannot read {bits} bits into a byte");
+        self.check_remaining(bits)?;
+
+        if bits == 0 {
+            return Ok(0);
+        }
+
         let byte_offset = self.head >> 3;
         let bit_offset = self.head & 0x7;
 
@@ -71,6 +98,13 @@ impl<'ctx, 'buffer, T> ErasedReadBitStream for InputMemoryStream<'ctx, 'buffer,
     }
 
     fn read_any_bits(&mut self, v: &mut [u8], mut bits: usize) -> Result<(), Self::Error> {
+        assert!(
+            bits <= v.len() * 8,
+            "cannot read {bits} bits into {} bytes",
+            v.len()
+        );
+        self.check_remaining(bits)?;
+
         let mut idx = 0;
 
         while bits > 8 {
@@ -85,4 +119,186 @@ impl<'ctx, 'buffer, T> ErasedReadBitStream for InputMemoryStream<'ctx, 'buffer,
 
         Ok(())
     }
+
+    fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    fn reserve(&mut self, reservation: Reservation) -> Result<(), Self::Error> {
+        self.check_reservation(reservation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::io::{
+        bits::{ReadBitStream, WriteBitStream},
+        bytes::{ErasedWriteStream, ReadStream, WriteStream},
+    };
+
+    use super::{GameIoError, InputMemoryStream, OutputMemoryStream};
+
+    #[derive(Clone, Debug)]
+    enum Write {
+        Bits(u64, usize),
+        U8(u8),
+        U16(u16),
+        U32(u32),
+        U64(u64),
+        Bytes(Vec<u8>),
+    }
+
+    impl Write {
+        /// The bits this write should produce, least significant first.
+        fn expected_bits(&self) -> Vec<bool> {
+            let (bytes, bits) = match self {
+                Write::Bits(v, bits) => (v.to_le_bytes().to_vec(), *bits),
+                Write::U8(v) => (vec![*v], 8),
+                Write::U16(v) => (v.to_le_bytes().to_vec(), 16),
+                Write::U32(v) => (v.to_le_bytes().to_vec(), 32),
+                Write::U64(v) => (v.to_le_bytes().to_vec(), 64),
+                Write::Bytes(v) => (v.clone(), v.len() * 8),
+            };
+
+            (0..bits)
+                .map(|bit| bytes[bit / 8] >> (bit % 8) & 1 == 1)
+                .collect()
+        }
+    }
+
+    fn write_strategy() -> impl Strategy<Value = Write> {
+        prop_oneof![
+            (any::<u64>(), 0..=64usize).prop_map(|(v, bits)| Write::Bits(v, bits)),
+            any::<u8>().prop_map(Write::U8),
+            any::<u16>().prop_map(Write::U16),
+            any::<u32>().prop_map(Write::U32),
+            any::<u64>().prop_map(Write::U64),
+            prop::collection::vec(any::<u8>(), 0..12).prop_map(Write::Bytes),
+        ]
+    }
+
+    fn pack(bits: &[bool]) -> Vec<u8> {
+        bits.chunks(8)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (idx, &bit)| byte | (bit as u8) << idx)
+            })
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn mixed_writes_match_a_bit_by_bit_model(
+            prefix in prop::collection::vec(any::<u8>(), 0..4),
+            writes in prop::collection::vec(write_strategy(), 0..32),
+        ) {
+            let mut ctx = ();
+            let mut buf = prefix.clone();
+            let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+            let mut expected: Vec<bool> = (0..prefix.len() * 8)
+                .map(|bit| prefix[bit / 8] >> (bit % 8) & 1 == 1)
+                .collect();
+
+            for write in &writes {
+                match write {
+                    Write::Bits(v, bits) => output.write_u64_bits(*v, *bits).unwrap(),
+                    Write::U8(v) => output.write_u8(*v).unwrap(),
+                    Write::U16(v) => output.write_u16(*v).unwrap(),
+                    Write::U32(v) => output.write_u32(*v).unwrap(),
+                    Write::U64(v) => output.write_u64(*v).unwrap(),
+                    Write::Bytes(v) => output.write_any(v).unwrap(),
+                }
+                expected.extend(write.expected_bits());
+            }
+
+            prop_assert_eq!(&buf, &pack(&expected));
+
+            let mut input = InputMemoryStream::new(&buf, &mut ctx);
+            let read: Vec<u8> = prefix.iter().map(|_| input.read_u8().unwrap()).collect();
+            prop_assert_eq!(read, prefix);
+
+            for write in &writes {
+                match write {
+                    Write::Bits(v, bits) => {
+                        let mask = u64::MAX.checked_shr(64 - *bits as u32).unwrap_or(0);
+                        prop_assert_eq!(input.read_u64_bits(*bits).unwrap(), v & mask);
+                    }
+                    Write::U8(v) => prop_assert_eq!(input.read_u8().unwrap(), *v),
+                    Write::U16(v) => prop_assert_eq!(input.read_u16().unwrap(), *v),
+                    Write::U32(v) => prop_assert_eq!(input.read_u32().unwrap(), *v),
+                    Write::U64(v) => prop_assert_eq!(input.read_u64().unwrap(), *v),
+                    Write::Bytes(v) => {
+                        let read: Vec<u8> = v.iter().map(|_| input.read_u8().unwrap()).collect();
+                        prop_assert_eq!(&read, v);
+                    }
+                }
+            }
+
+            prop_assert!(input.remaining_bit_count() < 8);
+        }
+    }
+
+    #[test]
+    fn reads_past_the_end_return_eof() {
+        let mut ctx = ();
+        let buf = [0xAB, 0xCD];
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        assert!(matches!(
+            input.read_u32_bits(17),
+            Err(GameIoError::UnexpectedEof(17, 16))
+        ));
+
+        input.read_u8_bits(5).unwrap();
+        assert!(matches!(
+            input.read_u16_bits(12),
+            Err(GameIoError::UnexpectedEof(12, 11))
+        ));
+        assert!(matches!(
+            input.read_u16(),
+            Err(GameIoError::UnexpectedEof(16, 11))
+        ));
+        assert_eq!(input.read_u16_bits(11).unwrap(), 0xCD << 3 | 0xAB >> 5);
+        assert!(matches!(
+            input.read_bool_bits(1),
+            Err(GameIoError::UnexpectedEof(1, 0))
+        ));
+        assert!(matches!(
+            input.read_u8(),
+            Err(GameIoError::UnexpectedEof(8, 0))
+        ));
+        assert_eq!(input.read_u8_bits(0).unwrap(), 0);
+    }
+
+    #[test]
+    fn truncated_input_never_panics() {
+        let mut ctx = ();
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        output.write_bool_bits(true, 1).unwrap();
+        output.write_u32_bits(0x1234_5678, 29).unwrap();
+        output.write_var_u64(1 << 40).unwrap();
+        output.write_f64(1.5).unwrap();
+        output.write_i16_bits(-7, 11).unwrap();
+
+        for len in 0..buf.len() {
+            let mut input = InputMemoryStream::new(&buf[..len], &mut ctx);
+            let result = (|| {
+                input.read_bool_bits(1)?;
+                input.read_u32_bits(29)?;
+                input.read_var_u64()?;
+                input.read_f64()?;
+                input.read_i16_bits(11)
+            })();
+
+            assert!(
+                matches!(result, Err(GameIoError::UnexpectedEof(..))),
+                "{len} bytes"
+            );
+        }
+    }
 }
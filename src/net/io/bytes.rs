@@ -1,4 +1,5 @@
 use crate::io::{
+    ByteOrder, Reservation,
     bits::{ErasedReadBitStream, ErasedWriteBitStream},
     bytes::{ErasedReadStream, ErasedWriteStream},
 };
@@ -9,7 +10,7 @@ impl<'ctx, 'buffer, T> ErasedWriteStream for OutputMemoryStream<'ctx, 'buffer, T
     type Error = GameIoError;
 
     fn write_any(&mut self, v: &[u8]) -> Result<(), Self::Error> {
-        if self.head % 8 == 0 {
+        if self.head.is_multiple_of(8) {
             self.buffer
                 .try_reserve(v.len())
                 .map_err(|_| GameIoError::Oom)?;
@@ -21,30 +22,34 @@ impl<'ctx, 'buffer, T> ErasedWriteStream for OutputMemoryStream<'ctx, 'buffer, T
             self.write_any_bits(v, v.len() * 8)
         }
     }
+
+    fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
 }
 
 impl<T> ErasedReadStream for InputMemoryStream<'_, '_, T> {
     type Error = GameIoError;
 
     fn read_any(&mut self, v: &mut [u8]) -> Result<(), Self::Error> {
-        let new_head = self.head.checked_add(v.len() * 8).ok_or_else(|| {
-            GameIoError::UnexpectedEof(v.len() * 8, self.buffer.len() * 8 - self.head)
-        })?;
-
-        if self.head < new_head {
-            if self.head % 8 == 0 {
-                let begin = self.head >> 3;
-                v.copy_from_slice(&self.buffer[begin..(begin + v.len())]);
-                self.head = new_head;
-                Ok(())
-            } else {
-                self.read_any_bits(v, v.len() * 8)
-            }
+        let bits = v.len() * 8;
+        self.check_remaining(bits)?;
+
+        if self.head.is_multiple_of(8) {
+            let begin = self.head >> 3;
+            v.copy_from_slice(&self.buffer[begin..(begin + v.len())]);
+            self.head += bits;
+            Ok(())
         } else {
-            Err(GameIoError::UnexpectedEof(
-                v.len() * 8,
-                self.buffer.len() * 8 - self.head,
-            ))
+            self.read_any_bits(v, bits)
         }
     }
+
+    fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    fn reserve(&mut self, reservation: Reservation) -> Result<(), Self::Error> {
+        self.check_reservation(reservation)
+    }
 }
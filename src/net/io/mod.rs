@@ -3,11 +3,37 @@ pub mod bytes;
 
 use std::string::FromUtf8Error;
 
+use crate::io::{ByteOrder, Reservation, SizeOverflow, VarIntOverflow};
+
+use super::network::PacketType;
+
 #[derive(Debug, Clone)]
 pub enum GameIoError {
     Utf8Error(FromUtf8Error),
     UnregisteredGameObject(usize),
+    UnregisteredClass(u32),
+    InvalidTypeInfo(&'static str),
     UnexpectedEof(usize, usize),
+    VarIntOverflow,
+    /// A `usize` or `isize` on the wire that does not fit the local pointer width.
+    SizeOverflow(u64),
+    /// A byte that does not name any variant of the enum it is decoded into.
+    InvalidEnumDiscriminant(&'static str, u8),
+    /// A length on the wire that exceeds the named [`DecodeLimits`] field. Holds the requested
+    /// amount.
+    LimitExceeded(&'static str, usize),
+    /// A packet whose header does not start with [`PROTOCOL_ID`], so it is not ours.
+    ///
+    /// [`PROTOCOL_ID`]: super::packet::PROTOCOL_ID
+    ForeignProtocol(u32),
+    /// A packet from a peer speaking another [`PROTOCOL_VERSION`].
+    ///
+    /// [`PROTOCOL_VERSION`]: super::packet::PROTOCOL_VERSION
+    ProtocolVersionMismatch(u16),
+    /// A packet type with no handler registered in the [`PacketDispatcher`].
+    ///
+    /// [`PacketDispatcher`]: super::packet::PacketDispatcher
+    UnhandledPacket(PacketType),
     Oom,
 }
 
@@ -17,21 +43,63 @@ impl From<FromUtf8Error> for GameIoError {
     }
 }
 
+impl From<VarIntOverflow> for GameIoError {
+    fn from(_: VarIntOverflow) -> Self {
+        Self::VarIntOverflow
+    }
+}
+
+impl From<SizeOverflow> for GameIoError {
+    fn from(value: SizeOverflow) -> Self {
+        Self::SizeOverflow(value.0)
+    }
+}
+
 pub struct OutputMemoryStream<'ctx, 'buffer, T> {
     pub ctx: &'ctx mut T,
 
     buffer: &'buffer mut Vec<u8>,
     head: usize,
+    byte_order: ByteOrder,
 }
 
 impl<'ctx, 'buffer, T> OutputMemoryStream<'ctx, 'buffer, T> {
+    /// Appends to whatever `buffer` already contains.
     pub fn new(buffer: &'buffer mut Vec<u8>, ctx: &'ctx mut T) -> Self {
         Self {
+            head: buffer.len() * 8,
             buffer,
-            head: 0,
+            byte_order: ByteOrder::default(),
             ctx,
         }
     }
+
+    pub fn with_byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.byte_order = byte_order;
+        self
+    }
+}
+
+/// Bounds on what an [`InputMemoryStream`] allocates for length-prefixed values, so that a
+/// small packet cannot request an arbitrarily large allocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Most elements in a single `Vec`.
+    pub max_collection_len: usize,
+    /// Most bytes in a single `String`.
+    pub max_string_bytes: usize,
+    /// Most bytes allocated for collections and strings over the whole stream.
+    pub max_allocation: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_collection_len: 1 << 16,
+            max_string_bytes: 1 << 16,
+            max_allocation: 1 << 20,
+        }
+    }
 }
 
 pub struct InputMemoryStream<'ctx, 'buffer, T> {
@@ -39,6 +107,9 @@ pub struct InputMemoryStream<'ctx, 'buffer, T> {
 
     buffer: &'buffer [u8],
     head: usize,
+    byte_order: ByteOrder,
+    limits: DecodeLimits,
+    allocated: usize,
 }
 
 impl<'ctx, 'buffer, T> InputMemoryStream<'ctx, 'buffer, T> {
@@ -46,11 +117,156 @@ impl<'ctx, 'buffer, T> InputMemoryStream<'ctx, 'buffer, T> {
         Self {
             buffer,
             head: 0,
+            byte_order: ByteOrder::default(),
+            limits: DecodeLimits::default(),
+            allocated: 0,
             ctx,
         }
     }
 
+    pub fn with_byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.byte_order = byte_order;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     pub fn remaining_bit_count(&self) -> usize {
         self.buffer.len() * 8 - self.head
     }
+
+    fn check_remaining(&self, bits: usize) -> Result<(), GameIoError> {
+        let remaining = self.remaining_bit_count();
+        if bits > remaining {
+            return Err(GameIoError::UnexpectedEof(bits, remaining));
+        }
+
+        Ok(())
+    }
+
+    /// Checks a length read from the wire against the limits and the remaining input, and counts
+    /// it towards [`DecodeLimits::max_allocation`].
+    fn check_reservation(&mut self, reservation: Reservation) -> Result<(), GameIoError> {
+        let (len, size, min_bits) = match reservation {
+            Reservation::Collection {
+                len,
+                size,
+                min_bits,
+            } => {
+                if len > self.limits.max_collection_len {
+                    return Err(GameIoError::LimitExceeded("max_collection_len", len));
+                }
+
+                (len, size, min_bits)
+            }
+            Reservation::String { len, min_bits } => {
+                if len > self.limits.max_string_bytes {
+                    return Err(GameIoError::LimitExceeded("max_string_bytes", len));
+                }
+
+                (len, 1, min_bits)
+            }
+        };
+
+        self.check_remaining(len.saturating_mul(min_bits))?;
+
+        let allocated = self.allocated.saturating_add(len.saturating_mul(size));
+        if allocated > self.limits.max_allocation {
+            return Err(GameIoError::LimitExceeded("max_allocation", allocated));
+        }
+
+        self.allocated = allocated;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{
+        bits::BitReadable,
+        bytes::{Readable, Writable, WriteStream},
+    };
+
+    use super::{DecodeLimits, GameIoError, InputMemoryStream, OutputMemoryStream};
+
+    const LIMITS: DecodeLimits = DecodeLimits {
+        max_collection_len: 4,
+        max_string_bytes: 3,
+        max_allocation: 32,
+    };
+
+    fn encode<T: for<'a, 'b> Writable<OutputMemoryStream<'a, 'b, ()>>>(v: T) -> Vec<u8> {
+        let mut buf = vec![];
+        v.write_byte(&mut OutputMemoryStream::new(&mut buf, &mut ()))
+            .unwrap();
+        buf
+    }
+
+    fn decode<T: for<'a, 'b> Readable<InputMemoryStream<'a, 'b, ()>>>(
+        buf: &[u8],
+    ) -> Result<T, GameIoError> {
+        T::read_byte(&mut InputMemoryStream::new(buf, &mut ()).with_limits(LIMITS))
+    }
+
+    #[test]
+    fn lengths_within_the_limits_are_read() {
+        assert_eq!(
+            decode::<Vec<u32>>(&encode(vec![1u32, 2, 3, 4])).unwrap(),
+            [1, 2, 3, 4]
+        );
+        assert_eq!(decode::<String>(&encode("abc".to_string())).unwrap(), "abc");
+    }
+
+    #[test]
+    fn lengths_over_the_limits_are_rejected() {
+        assert!(matches!(
+            decode::<Vec<u8>>(&encode(vec![0u8; 5])),
+            Err(GameIoError::LimitExceeded("max_collection_len", 5))
+        ));
+        assert!(matches!(
+            decode::<String>(&encode("abcd".to_string())),
+            Err(GameIoError::LimitExceeded("max_string_bytes", 4))
+        ));
+
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        output.write_var_usize(4).unwrap();
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx).with_limits(LIMITS);
+        assert!(matches!(
+            Vec::<u8>::read_bits(&mut input, 4),
+            Err(GameIoError::UnexpectedEof(4, 0))
+        ));
+    }
+
+    #[test]
+    fn allocations_add_up_over_the_stream() {
+        let mut buf = encode(vec![0u32; 4]);
+        buf.extend(encode(vec![0u32; 4]));
+        buf.extend(encode(vec![0u32; 1]));
+
+        let mut ctx = ();
+        let mut input = InputMemoryStream::new(&buf, &mut ctx).with_limits(LIMITS);
+        assert!(Vec::<u32>::read_byte(&mut input).is_ok());
+        assert!(Vec::<u32>::read_byte(&mut input).is_ok());
+        assert!(matches!(
+            Vec::<u32>::read_byte(&mut input),
+            Err(GameIoError::LimitExceeded("max_allocation", 36))
+        ));
+    }
+
+    #[test]
+    fn lengths_past_the_end_of_the_input_are_rejected() {
+        let buf = encode(vec![0u16; 3]);
+
+        assert!(matches!(
+            decode::<Vec<u16>>(&buf[..3]),
+            Err(GameIoError::UnexpectedEof(24, 16))
+        ));
+    }
 }
@@ -0,0 +1,1257 @@
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    GameObject,
+    io::{
+        bytes::{Readable, Writable},
+        varint::VarU32,
+    },
+    linking_context::LinkingContext,
+};
+
+use super::{
+    io::{GameIoError, InputMemoryStream, OutputMemoryStream},
+    network::{ObjectRegistry, PacketType, ReplicationManager},
+    packet::{PacketBuilder, PacketDispatcher},
+    transport::{Peer, TransportError, UdpTransport},
+};
+
+/// How long a connecting client waits for a welcome before saying hello again.
+pub const DEFAULT_HELLO_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long either side stays quiet before sending a [`PacketType::Heartbeat`].
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long either side waits for a packet before giving up on the connection.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many clients a server accepts at once.
+pub const DEFAULT_MAX_CLIENTS: usize = 32;
+
+/// Where a [`NetworkManager`] gets the current time from.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetworkEvent {
+    /// On the server, `addr` said hello and was assigned `player_id`.
+    ClientConnected {
+        addr: SocketAddr,
+        player_id: u32,
+        name: String,
+    },
+    /// On the server, `addr` disconnected or was disconnected.
+    ClientDisconnected { addr: SocketAddr, player_id: u32 },
+    /// On the server, nothing was heard from `addr` for the timeout and it was dropped.
+    ClientTimedOut { addr: SocketAddr, player_id: u32 },
+    /// On a client, the server welcomed us as `player_id`.
+    Connected { player_id: u32 },
+    /// On a client, the server closed the connection.
+    Disconnected,
+    /// On a client, nothing was heard from the server for the timeout.
+    TimedOut,
+}
+
+#[derive(Debug)]
+pub enum NetworkError {
+    Io(GameIoError),
+    Transport(TransportError),
+    /// Sending to these addresses failed. Everyone else was sent to.
+    Send(Vec<(SocketAddr, TransportError)>),
+}
+
+impl NetworkError {
+    fn from_failed_sends(failed: Vec<(SocketAddr, TransportError)>) -> Result<(), Self> {
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(Self::Send(failed))
+        }
+    }
+}
+
+impl From<GameIoError> for NetworkError {
+    fn from(value: GameIoError) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<TransportError> for NetworkError {
+    fn from(value: TransportError) -> Self {
+        Self::Transport(value)
+    }
+}
+
+/// What the server keeps for each connected client. Objects are replicated to every client
+/// through its own [`LinkingContext`], so network ids are per connection.
+pub struct ClientProxy {
+    pub name: String,
+    pub player_id: u32,
+    /// When the last valid packet from this client was handled.
+    pub last_received: Instant,
+
+    last_sent: Instant,
+    ctx: LinkingContext,
+    replication: ReplicationManager,
+}
+
+struct ServerState {
+    clients: HashMap<SocketAddr, ClientProxy>,
+    max_clients: usize,
+    next_player_id: u32,
+    outbox: Vec<(SocketAddr, Vec<u8>)>,
+    /// Clients that disconnected while handling packets, whose transport peers are still kept.
+    disconnected: Vec<SocketAddr>,
+    events: Vec<NetworkEvent>,
+    /// When the current update started.
+    now: Instant,
+}
+
+struct ClientState {
+    server: SocketAddr,
+    name: String,
+    connection: ConnectionState,
+    player_id: Option<u32>,
+    last_received: Option<Instant>,
+    last_sent: Option<Instant>,
+    replication: ReplicationManager,
+    registry: ObjectRegistry,
+    events: Vec<NetworkEvent>,
+}
+
+impl ClientState {
+    /// Forgets the connection along with everything that was replicated through it.
+    fn tear_down(&mut self, ctx: &mut LinkingContext) {
+        self.connection = ConnectionState::Disconnected;
+        self.player_id = None;
+        self.replication = ReplicationManager::new();
+        *ctx = LinkingContext::default();
+    }
+}
+
+enum Role {
+    Server {
+        state: ServerState,
+        dispatcher: PacketDispatcher<ServerState, ()>,
+    },
+    Client {
+        state: Box<ClientState>,
+        ctx: LinkingContext,
+        dispatcher: PacketDispatcher<ClientState, LinkingContext>,
+    },
+}
+
+/// Runs either end of the connection handshake on top of a [`UdpTransport`]:
+///
+/// 1. A client sends [`PacketType::Hello`] with its name, again every hello interval until it
+///    hears back.
+/// 2. The server assigns a player id, sets up a [`ClientProxy`] and answers with
+///    [`PacketType::Welcome`]. A repeated hello gets the same player id. A full server answers
+///    with [`PacketType::Disconnect`] instead.
+/// 3. While connected, a side that has sent nothing for the heartbeat interval sends a
+///    [`PacketType::Heartbeat`]. A side that hears nothing for the timeout drops the connection.
+/// 4. Either side ends the connection with [`PacketType::Disconnect`].
+///
+/// Call [`Self::update`] once per tick.
+pub struct NetworkManager {
+    transport: UdpTransport,
+    role: Role,
+    clock: Box<dyn Clock>,
+    hello_interval: Duration,
+    heartbeat_interval: Duration,
+    timeout: Duration,
+}
+
+impl NetworkManager {
+    pub fn server(addr: impl ToSocketAddrs) -> Result<Self, NetworkError> {
+        let mut dispatcher = PacketDispatcher::new();
+        dispatcher
+            .register(PacketType::Hello, server_on_hello)
+            .register(PacketType::Disconnect, server_on_disconnect)
+            .register(PacketType::Heartbeat, |_, _, _| Ok(()));
+
+        Ok(Self::new(
+            UdpTransport::bind(addr)?,
+            Role::Server {
+                state: ServerState {
+                    clients: HashMap::new(),
+                    max_clients: DEFAULT_MAX_CLIENTS,
+                    next_player_id: 1,
+                    outbox: vec![],
+                    disconnected: vec![],
+                    events: vec![],
+                    now: Instant::now(),
+                },
+                dispatcher,
+            },
+        ))
+    }
+
+    /// Binds to `addr` and starts connecting to `server` on the first [`Self::update`].
+    /// `registry` creates the objects the server replicates.
+    pub fn client(
+        addr: impl ToSocketAddrs,
+        server: SocketAddr,
+        name: impl Into<String>,
+        registry: ObjectRegistry,
+    ) -> Result<Self, NetworkError> {
+        let mut dispatcher = PacketDispatcher::new();
+        dispatcher
+            .register(PacketType::Welcome, client_on_welcome)
+            .register(PacketType::ReplicationData, client_on_replication_data)
+            .register(PacketType::Disconnect, client_on_disconnect)
+            .register(PacketType::Heartbeat, |_, _, _| Ok(()));
+
+        Ok(Self::new(
+            UdpTransport::bind(addr)?,
+            Role::Client {
+                state: Box::new(ClientState {
+                    server,
+                    name: name.into(),
+                    connection: ConnectionState::Connecting,
+                    player_id: None,
+                    last_received: None,
+                    last_sent: None,
+                    replication: ReplicationManager::new(),
+                    registry,
+                    events: vec![],
+                }),
+                ctx: LinkingContext::default(),
+                dispatcher,
+            },
+        ))
+    }
+
+    fn new(transport: UdpTransport, role: Role) -> Self {
+        Self {
+            transport,
+            role,
+            clock: Box::new(SystemClock),
+            hello_interval: DEFAULT_HELLO_INTERVAL,
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Replaces [`SystemClock`], e.g. with one a test advances by hand.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn with_hello_interval(mut self, hello_interval: Duration) -> Self {
+        self.hello_interval = hello_interval;
+        self
+    }
+
+    pub fn with_heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Once a server has `max_clients`, hellos from new addresses are answered with
+    /// [`PacketType::Disconnect`]. Does nothing on a client.
+    pub fn with_max_clients(mut self, max_clients: usize) -> Self {
+        if let Role::Server { state, .. } = &mut self.role {
+            state.max_clients = max_clients;
+        }
+        self
+    }
+
+    /// See [`UdpTransport::with_mtu`].
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.transport = self.transport.with_mtu(mtu);
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, NetworkError> {
+        Ok(self.transport.local_addr()?)
+    }
+
+    pub fn is_server(&self) -> bool {
+        matches!(self.role, Role::Server { .. })
+    }
+
+    /// The client's connection to its server. A server is always [`ConnectionState::Connected`],
+    /// see [`Self::client_state`] for its clients.
+    pub fn connection_state(&self) -> ConnectionState {
+        match &self.role {
+            Role::Server { .. } => ConnectionState::Connected,
+            Role::Client { state, .. } => state.connection,
+        }
+    }
+
+    /// The player id the server assigned to this client, once welcomed.
+    pub fn player_id(&self) -> Option<u32> {
+        match &self.role {
+            Role::Server { .. } => None,
+            Role::Client { state, .. } => state.player_id,
+        }
+    }
+
+    /// When this client last handled a valid packet from its server. See
+    /// [`ClientProxy::last_received`] for the clients of a server.
+    pub fn last_received(&self) -> Option<Instant> {
+        match &self.role {
+            Role::Server { .. } => None,
+            Role::Client { state, .. } => state.last_received,
+        }
+    }
+
+    /// The addresses the transport currently keeps stats for.
+    pub fn peers(&self) -> impl Iterator<Item = &Peer> {
+        self.transport.peers()
+    }
+
+    /// The objects replicated to this client. `None` on a server.
+    pub fn linking_context(&self) -> Option<&LinkingContext> {
+        match &self.role {
+            Role::Server { .. } => None,
+            Role::Client { ctx, .. } => Some(ctx),
+        }
+    }
+
+    /// Every client connected to this server.
+    pub fn clients(&self) -> impl Iterator<Item = (SocketAddr, &ClientProxy)> {
+        let clients = match &self.role {
+            Role::Server { state, .. } => Some(state.clients.iter()),
+            Role::Client { .. } => None,
+        };
+
+        clients
+            .into_iter()
+            .flatten()
+            .map(|(addr, client)| (*addr, client))
+    }
+
+    pub fn client_state(&self, addr: SocketAddr) -> ConnectionState {
+        match &self.role {
+            Role::Server { state, .. } if state.clients.contains_key(&addr) => {
+                ConnectionState::Connected
+            }
+            _ => ConnectionState::Disconnected,
+        }
+    }
+
+    /// Handles every packet that arrived since the last call, drops connections that timed out,
+    /// sends whatever the handshake and heartbeats need and returns what happened. Malformed and
+    /// foreign packets are dropped and do not count as hearing from their sender.
+    ///
+    /// On a server, packets that failed to send stay queued for the next call, unless they can
+    /// never fit the MTU, and the events are kept until a call succeeds.
+    pub fn update(&mut self) -> Result<Vec<NetworkEvent>, NetworkError> {
+        let now = self.clock.now();
+        let packets = self.transport.receive_packets(now)?;
+
+        match &mut self.role {
+            Role::Server { state, dispatcher } => {
+                state.now = now;
+
+                for packet in &packets {
+                    let mut ctx = ();
+                    let mut input = packet.stream(&mut ctx);
+                    if dispatcher.dispatch(state, packet.from, &mut input).is_ok()
+                        && let Some(client) = state.clients.get_mut(&packet.from)
+                    {
+                        client.last_received = now;
+                    }
+                }
+
+                for addr in state.disconnected.drain(..) {
+                    self.transport.remove_peer(addr);
+                }
+
+                state.clients.retain(|&addr, client| {
+                    let alive = now.duration_since(client.last_received) < self.timeout;
+                    if !alive {
+                        state.events.push(NetworkEvent::ClientTimedOut {
+                            addr,
+                            player_id: client.player_id,
+                        });
+                        state.outbox.retain(|(to, _)| *to != addr);
+                        self.transport.remove_peer(addr);
+                    }
+
+                    alive
+                });
+
+                let heartbeat = PacketBuilder::new(PacketType::Heartbeat).finish();
+                for (&addr, client) in &state.clients {
+                    // Anything still queued for the client does as well as a heartbeat.
+                    if now.duration_since(client.last_sent) >= self.heartbeat_interval
+                        && !state.outbox.iter().any(|(to, _)| *to == addr)
+                    {
+                        state.outbox.push((addr, heartbeat.clone()));
+                    }
+                }
+
+                let mut failed = vec![];
+                state.outbox.retain(|(addr, packet)| {
+                    match self.transport.send(*addr, packet) {
+                        Ok(()) => {
+                            if let Some(client) = state.clients.get_mut(addr) {
+                                client.last_sent = now;
+                            }
+
+                            false
+                        }
+                        // Retrying will not make it fit.
+                        Err(err @ TransportError::PacketTooLarge(..)) => {
+                            failed.push((*addr, err));
+                            false
+                        }
+                        Err(err) => {
+                            failed.push((*addr, err));
+                            true
+                        }
+                    }
+                });
+                NetworkError::from_failed_sends(failed)?;
+
+                Ok(std::mem::take(&mut state.events))
+            }
+            Role::Client {
+                state,
+                ctx,
+                dispatcher,
+            } => {
+                for packet in &packets {
+                    // Set per packet, since a disconnect tears the context down.
+                    ctx.set_now(now);
+                    let mut input = packet.stream(ctx);
+                    if dispatcher.dispatch(state, packet.from, &mut input).is_ok()
+                        && packet.from == state.server
+                    {
+                        state.last_received = Some(now);
+                    }
+                }
+
+                if state.connection == ConnectionState::Disconnected {
+                    self.transport.remove_peer(state.server);
+                    return Ok(std::mem::take(&mut state.events));
+                }
+
+                // A client that never heard back times out counting from its first hello.
+                let last_received = *state.last_received.get_or_insert(now);
+                if now.duration_since(last_received) >= self.timeout {
+                    state.tear_down(ctx);
+                    state.events.push(NetworkEvent::TimedOut);
+                    self.transport.remove_peer(state.server);
+
+                    return Ok(std::mem::take(&mut state.events));
+                }
+
+                let (packet_type, interval) = match state.connection {
+                    ConnectionState::Connecting => (PacketType::Hello, self.hello_interval),
+                    _ => (PacketType::Heartbeat, self.heartbeat_interval),
+                };
+
+                if state
+                    .last_sent
+                    .is_none_or(|sent| now.duration_since(sent) >= interval)
+                {
+                    let mut builder = PacketBuilder::new(packet_type);
+                    if packet_type == PacketType::Hello {
+                        state.name.write_byte(&mut builder.stream(&mut ()))?;
+                    }
+
+                    self.transport.send(state.server, &builder.finish())?;
+                    state.last_sent = Some(now);
+                }
+
+                Ok(std::mem::take(&mut state.events))
+            }
+        }
+    }
+
+    /// Sends the state of `gos` to every connected client, even if sending to some of them
+    /// fails. Clients have nothing to send, so this does nothing on them.
+    pub fn replicate_world_state(
+        &mut self,
+        gos: &[Arc<dyn GameObject>],
+    ) -> Result<(), NetworkError> {
+        let Role::Server { state, .. } = &mut self.role else {
+            return Ok(());
+        };
+
+        let now = self.clock.now();
+        let mut failed = vec![];
+        for (addr, client) in &mut state.clients {
+            let mut buf = vec![];
+            let mut output = OutputMemoryStream::new(&mut buf, &mut client.ctx);
+            client.replication.replicate_world_state(&mut output, gos)?;
+            match self.transport.send(*addr, &buf) {
+                Ok(()) => client.last_sent = now,
+                Err(err) => failed.push((*addr, err)),
+            }
+        }
+
+        NetworkError::from_failed_sends(failed)
+    }
+
+    /// On a client, tells the server and drops everything replicated so far. On a server, does
+    /// the same for every client.
+    pub fn disconnect(&mut self) -> Result<(), NetworkError> {
+        let packet = PacketBuilder::new(PacketType::Disconnect).finish();
+
+        match &mut self.role {
+            Role::Server { state, .. } => {
+                let mut failed = vec![];
+
+                for (addr, client) in state.clients.drain() {
+                    if let Err(err) = self.transport.send(addr, &packet) {
+                        failed.push((addr, err));
+                    }
+                    self.transport.remove_peer(addr);
+                    state.events.push(NetworkEvent::ClientDisconnected {
+                        addr,
+                        player_id: client.player_id,
+                    });
+                }
+                state.outbox.clear();
+
+                NetworkError::from_failed_sends(failed)
+            }
+            Role::Client { state, ctx, .. } => {
+                let result = if state.connection == ConnectionState::Disconnected {
+                    Ok(())
+                } else {
+                    self.transport.send(state.server, &packet)
+                };
+
+                state.tear_down(ctx);
+                self.transport.remove_peer(state.server);
+
+                Ok(result?)
+            }
+        }
+    }
+
+    /// Disconnects a single client of this server. Returns whether `addr` was connected.
+    pub fn disconnect_client(&mut self, addr: SocketAddr) -> Result<bool, NetworkError> {
+        let Role::Server { state, .. } = &mut self.role else {
+            return Ok(false);
+        };
+
+        let Some(client) = state.clients.remove(&addr) else {
+            return Ok(false);
+        };
+
+        state.events.push(NetworkEvent::ClientDisconnected {
+            addr,
+            player_id: client.player_id,
+        });
+        state.outbox.retain(|(to, _)| *to != addr);
+        let result = self
+            .transport
+            .send(addr, &PacketBuilder::new(PacketType::Disconnect).finish());
+        self.transport.remove_peer(addr);
+        result?;
+
+        Ok(true)
+    }
+}
+
+fn server_on_hello(
+    server: &mut ServerState,
+    from: SocketAddr,
+    input: &mut InputMemoryStream<'_, '_, ()>,
+) -> Result<(), GameIoError> {
+    let name = String::read_byte(input)?;
+
+    // A known address is still saying hello because our welcome got lost.
+    let player_id = match server.clients.get(&from) {
+        Some(client) => client.player_id,
+        None if server.clients.len() >= server.max_clients => {
+            let packet = PacketBuilder::new(PacketType::Disconnect).finish();
+            server.outbox.push((from, packet));
+
+            return Ok(());
+        }
+        None => {
+            let player_id = server.next_player_id;
+            server.next_player_id += 1;
+            server.events.push(NetworkEvent::ClientConnected {
+                addr: from,
+                player_id,
+                name: name.clone(),
+            });
+            server.clients.insert(
+                from,
+                ClientProxy {
+                    name,
+                    player_id,
+                    last_received: server.now,
+                    last_sent: server.now,
+                    ctx: LinkingContext::default(),
+                    replication: ReplicationManager::new(),
+                },
+            );
+
+            player_id
+        }
+    };
+
+    let mut builder = PacketBuilder::new(PacketType::Welcome);
+    VarU32(player_id).write_byte(&mut builder.stream(&mut ()))?;
+    server.outbox.push((from, builder.finish()));
+
+    Ok(())
+}
+
+fn server_on_disconnect(
+    server: &mut ServerState,
+    from: SocketAddr,
+    _: &mut InputMemoryStream<'_, '_, ()>,
+) -> Result<(), GameIoError> {
+    if let Some(client) = server.clients.remove(&from) {
+        server.events.push(NetworkEvent::ClientDisconnected {
+            addr: from,
+            player_id: client.player_id,
+        });
+        server.outbox.retain(|(to, _)| *to != from);
+        server.disconnected.push(from);
+    }
+
+    Ok(())
+}
+
+fn client_on_welcome(
+    client: &mut ClientState,
+    from: SocketAddr,
+    input: &mut InputMemoryStream<'_, '_, LinkingContext>,
+) -> Result<(), GameIoError> {
+    let player_id = VarU32::read_byte(input)?.0;
+
+    if from == client.server && client.connection == ConnectionState::Connecting {
+        client.connection = ConnectionState::Connected;
+        client.player_id = Some(player_id);
+        client.events.push(NetworkEvent::Connected { player_id });
+    }
+
+    Ok(())
+}
+
+fn client_on_replication_data(
+    client: &mut ClientState,
+    from: SocketAddr,
+    input: &mut InputMemoryStream<'_, '_, LinkingContext>,
+) -> Result<(), GameIoError> {
+    if from != client.server || client.connection != ConnectionState::Connected {
+        return Ok(());
+    }
+
+    client
+        .replication
+        .recv_replicated_objects(input, &client.registry)
+}
+
+fn client_on_disconnect(
+    client: &mut ClientState,
+    from: SocketAddr,
+    input: &mut InputMemoryStream<'_, '_, LinkingContext>,
+) -> Result<(), GameIoError> {
+    if from == client.server && client.connection != ConnectionState::Disconnected {
+        client.tear_down(input.ctx);
+        client.events.push(NetworkEvent::Disconnected);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        rc::Rc,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        GameObject, Replicated,
+        io::{
+            bytes::{Readable, Writable},
+            varint::VarU32,
+        },
+        linking_context::LinkingContext,
+        net::{
+            network::{ObjectRegistry, PacketType},
+            packet::{PacketBuilder, PacketHeader},
+            transport::{Packet, TransportError, UdpTransport},
+        },
+        reflect::Reflect,
+    };
+
+    use super::{Clock, ConnectionState, NetworkError, NetworkEvent, NetworkManager};
+
+    #[derive(Debug, Default, Clone, Reflect)]
+    #[reflect(type_id = 1)]
+    struct Cat {
+        name: String,
+    }
+
+    /// Only moves when a test advances it, and is shared by every manager of that test.
+    #[derive(Clone)]
+    struct ManualClock(Rc<Cell<Instant>>);
+
+    impl ManualClock {
+        fn new() -> Self {
+            Self(Rc::new(Cell::new(Instant::now())))
+        }
+
+        fn advance(&self, by: Duration) {
+            self.0.set(self.0.get() + by);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+    }
+
+    fn server() -> NetworkManager {
+        NetworkManager::server("127.0.0.1:0").unwrap()
+    }
+
+    fn client(server: &NetworkManager, name: &str) -> NetworkManager {
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>();
+
+        let server_addr = server.local_addr().unwrap();
+        NetworkManager::client("127.0.0.1:0", server_addr, name, registry).unwrap()
+    }
+
+    /// Updates every manager until `done` holds, as the game loop would over several ticks.
+    /// Returns the events each manager reported.
+    fn tick_until(
+        managers: &mut [&mut NetworkManager],
+        mut done: impl FnMut(&[&mut NetworkManager], &[Vec<NetworkEvent>]) -> bool,
+    ) -> Vec<Vec<NetworkEvent>> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut events = vec![vec![]; managers.len()];
+
+        while !done(managers, &events) && Instant::now() < deadline {
+            for (manager, events) in managers.iter_mut().zip(&mut events) {
+                events.extend(manager.update().unwrap());
+            }
+
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        events
+    }
+
+    fn connect(server: &mut NetworkManager, client: &mut NetworkManager) {
+        tick_until(&mut [server, client], |managers, _| {
+            managers[1].connection_state() == ConnectionState::Connected
+        });
+        assert_eq!(client.connection_state(), ConnectionState::Connected);
+    }
+
+    /// Polls a raw transport until `count` packets have arrived.
+    fn receive(transport: &mut UdpTransport, count: usize) -> Vec<Packet> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut packets = vec![];
+
+        while packets.len() < count && Instant::now() < deadline {
+            packets.extend(transport.receive_packets(Instant::now()).unwrap());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        packets
+    }
+
+    fn packet_type(packet: &Packet) -> PacketType {
+        let mut ctx = ();
+        PacketHeader::read_byte(&mut packet.stream(&mut ctx))
+            .unwrap()
+            .packet_type
+    }
+
+    fn cat_name(ctx: &LinkingContext, id: usize) -> Option<String> {
+        let go = ctx.get_game_object(id)?;
+        let mut name = None;
+        go.with_data(&mut |data| {
+            name = Some(unsafe { &*(data as *const Cat) }.name.clone());
+            Ok(())
+        })
+        .unwrap();
+
+        name
+    }
+
+    #[test]
+    fn handshake_assigns_a_player_id_to_every_client() {
+        let mut server = server();
+        let mut tom = client(&server, "Tom");
+        let mut jerry = client(&server, "Jerry");
+        assert_eq!(tom.connection_state(), ConnectionState::Connecting);
+        assert_eq!(tom.player_id(), None);
+
+        let events = tick_until(&mut [&mut server, &mut tom, &mut jerry], |managers, _| {
+            managers[1..]
+                .iter()
+                .all(|client| client.connection_state() == ConnectionState::Connected)
+        });
+
+        let tom_id = tom.player_id().unwrap();
+        let jerry_id = jerry.player_id().unwrap();
+        let mut ids = [tom_id, jerry_id];
+        ids.sort();
+        assert_eq!(ids, [1, 2]);
+        assert_eq!(events[1], [NetworkEvent::Connected { player_id: tom_id }]);
+        assert_eq!(
+            events[2],
+            [NetworkEvent::Connected {
+                player_id: jerry_id
+            }]
+        );
+
+        let tom_addr = tom.local_addr().unwrap();
+        assert!(events[0].contains(&NetworkEvent::ClientConnected {
+            addr: tom_addr,
+            player_id: tom_id,
+            name: "Tom".to_string(),
+        }));
+        assert_eq!(events[0].len(), 2);
+
+        assert_eq!(server.clients().count(), 2);
+        assert_eq!(server.client_state(tom_addr), ConnectionState::Connected);
+        let (_, proxy) = server
+            .clients()
+            .find(|(addr, _)| *addr == tom_addr)
+            .unwrap();
+        assert_eq!((proxy.name.as_str(), proxy.player_id), ("Tom", tom_id));
+    }
+
+    #[test]
+    fn hello_is_retried_until_welcomed() {
+        let mut fake_server = UdpTransport::bind("127.0.0.1:0").unwrap();
+        let server_addr = fake_server.local_addr().unwrap();
+        let mut client =
+            NetworkManager::client("127.0.0.1:0", server_addr, "Tom", ObjectRegistry::default())
+                .unwrap()
+                .with_hello_interval(Duration::from_millis(5));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut hellos = vec![];
+        while hellos.len() < 3 && Instant::now() < deadline {
+            client.update().unwrap();
+            hellos.extend(fake_server.receive_packets(Instant::now()).unwrap());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(hellos.len(), 3);
+        for hello in &hellos {
+            let mut ctx = ();
+            let mut input = hello.stream(&mut ctx);
+            let header = PacketHeader::read_byte(&mut input).unwrap();
+            assert_eq!(header.packet_type, PacketType::Hello);
+            assert_eq!(String::read_byte(&mut input).unwrap(), "Tom");
+        }
+
+        let mut welcome = PacketBuilder::new(PacketType::Welcome);
+        VarU32(7).write_byte(&mut welcome.stream(&mut ())).unwrap();
+        fake_server
+            .send(client.local_addr().unwrap(), &welcome.finish())
+            .unwrap();
+
+        let mut events = vec![];
+        while events.is_empty() && Instant::now() < deadline {
+            events = client.update().unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(events, [NetworkEvent::Connected { player_id: 7 }]);
+        assert_eq!(client.player_id(), Some(7));
+
+        // Whatever was already in flight is drained before checking that no more hellos come.
+        std::thread::sleep(Duration::from_millis(20));
+        fake_server.receive_packets(Instant::now()).unwrap();
+        client.update().unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(
+            fake_server
+                .receive_packets(Instant::now())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn repeated_hellos_get_the_same_player_id() {
+        let mut server = server();
+        let mut raw = UdpTransport::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+
+        for _ in 0..2 {
+            let mut hello = PacketBuilder::new(PacketType::Hello);
+            "Tom".write_byte(&mut hello.stream(&mut ())).unwrap();
+            raw.send(server_addr, &hello.finish()).unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut events = vec![];
+        let mut welcomes = vec![];
+        while welcomes.len() < 2 && Instant::now() < deadline {
+            events.extend(server.update().unwrap());
+            welcomes.extend(raw.receive_packets(Instant::now()).unwrap());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(server.clients().count(), 1);
+        assert_eq!(welcomes.len(), 2);
+        for welcome in &welcomes {
+            let mut ctx = ();
+            let mut input = welcome.stream(&mut ctx);
+            let header = PacketHeader::read_byte(&mut input).unwrap();
+            assert_eq!(header.packet_type, PacketType::Welcome);
+            assert_eq!(VarU32::read_byte(&mut input).unwrap().0, 1);
+        }
+    }
+
+    #[test]
+    fn world_state_reaches_connected_clients() {
+        let mut server = server();
+        let mut client = client(&server, "Tom");
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat {
+            name: "Felix".to_string(),
+        }));
+
+        // Nobody is connected yet, so there is nobody to send to.
+        server
+            .replicate_world_state(std::slice::from_ref(&cat))
+            .unwrap();
+        connect(&mut server, &mut client);
+
+        server.replicate_world_state(&[cat]).unwrap();
+        tick_until(&mut [&mut client], |managers, _| {
+            managers[0]
+                .linking_context()
+                .unwrap()
+                .get_game_object(0)
+                .is_some()
+        });
+
+        let ctx = client.linking_context().unwrap();
+        assert_eq!(cat_name(ctx, 0).as_deref(), Some("Felix"));
+    }
+
+    #[test]
+    fn full_server_turns_new_clients_away() {
+        let mut server = server().with_max_clients(1);
+        let mut tom = client(&server, "Tom");
+        let mut felix = client(&server, "Felix");
+        connect(&mut server, &mut tom);
+
+        let events = tick_until(&mut [&mut server, &mut felix], |managers, _| {
+            managers[1].connection_state() == ConnectionState::Disconnected
+        });
+        assert_eq!(events[1], [NetworkEvent::Disconnected]);
+        assert_eq!(felix.player_id(), None);
+        assert!(events[0].is_empty());
+        assert_eq!(server.clients().count(), 1);
+        assert_eq!(
+            server.client_state(felix.local_addr().unwrap()),
+            ConnectionState::Disconnected
+        );
+    }
+
+    #[test]
+    fn world_state_send_failures_are_reported_per_client() {
+        let mut server = server().with_mtu(64);
+        let mut tom = client(&server, "Tom");
+        let mut felix = client(&server, "Felix");
+        connect(&mut server, &mut tom);
+        connect(&mut server, &mut felix);
+
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat {
+            name: "Felix".repeat(20),
+        }));
+        let Err(NetworkError::Send(mut failed)) = server.replicate_world_state(&[cat]) else {
+            panic!("a world state over the MTU was sent");
+        };
+
+        failed.sort_by_key(|(addr, _)| *addr);
+        let mut addrs = [tom.local_addr().unwrap(), felix.local_addr().unwrap()];
+        addrs.sort();
+        assert_eq!(failed.len(), 2);
+        for ((addr, err), expected) in failed.iter().zip(addrs) {
+            assert_eq!(*addr, expected);
+            assert!(matches!(err, TransportError::PacketTooLarge(_, 64)));
+        }
+
+        assert_eq!(server.clients().count(), 2);
+        server.update().unwrap();
+    }
+
+    #[test]
+    fn client_disconnect_tears_down_both_sides() {
+        let mut server = server();
+        let mut client = client(&server, "Tom");
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        connect(&mut server, &mut client);
+        server.replicate_world_state(&[cat]).unwrap();
+        tick_until(&mut [&mut client], |managers, _| {
+            managers[0]
+                .linking_context()
+                .unwrap()
+                .get_game_object(0)
+                .is_some()
+        });
+
+        let client_addr = client.local_addr().unwrap();
+        let player_id = client.player_id().unwrap();
+        assert_eq!(server.peers().count(), 1);
+        assert_eq!(client.peers().count(), 1);
+        client.disconnect().unwrap();
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+        assert_eq!(client.player_id(), None);
+        assert_eq!(client.peers().count(), 0);
+        assert!(
+            client
+                .linking_context()
+                .unwrap()
+                .get_game_object(0)
+                .is_none()
+        );
+
+        let events = tick_until(&mut [&mut server], |_, events| !events[0].is_empty());
+        assert_eq!(
+            events[0],
+            [NetworkEvent::ClientDisconnected {
+                addr: client_addr,
+                player_id,
+            }]
+        );
+        assert_eq!(server.clients().count(), 0);
+        assert_eq!(server.peers().count(), 0);
+        assert_eq!(
+            server.client_state(client_addr),
+            ConnectionState::Disconnected
+        );
+    }
+
+    #[test]
+    fn server_disconnect_tears_down_the_client() {
+        let mut server = server();
+        let mut client = client(&server, "Tom");
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        connect(&mut server, &mut client);
+        server.replicate_world_state(&[cat]).unwrap();
+
+        let client_addr = client.local_addr().unwrap();
+        let player_id = client.player_id().unwrap();
+        assert!(server.disconnect_client(client_addr).unwrap());
+        assert!(!server.disconnect_client(client_addr).unwrap());
+        assert_eq!(server.peers().count(), 0);
+        assert_eq!(
+            server.update().unwrap(),
+            [NetworkEvent::ClientDisconnected {
+                addr: client_addr,
+                player_id,
+            }]
+        );
+
+        let events = tick_until(&mut [&mut client], |managers, _| {
+            managers[0].connection_state() == ConnectionState::Disconnected
+        });
+        assert_eq!(events[0].last(), Some(&NetworkEvent::Disconnected));
+        assert_eq!(client.player_id(), None);
+        assert_eq!(client.peers().count(), 0);
+        assert!(
+            client
+                .linking_context()
+                .unwrap()
+                .get_game_object(0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn idle_connections_are_kept_alive_by_heartbeats() {
+        let clock = ManualClock::new();
+        let mut server = server()
+            .with_clock(clock.clone())
+            .with_heartbeat_interval(Duration::from_secs(1))
+            .with_timeout(Duration::from_secs(3));
+        let mut client = client(&server, "Tom")
+            .with_clock(clock.clone())
+            .with_heartbeat_interval(Duration::from_secs(1))
+            .with_timeout(Duration::from_secs(3));
+        connect(&mut server, &mut client);
+        let client_addr = client.local_addr().unwrap();
+
+        for _ in 0..10 {
+            clock.advance(Duration::from_secs(1));
+            let now = clock.now();
+
+            let events = tick_until(&mut [&mut server, &mut client], |managers, _| {
+                let (_, proxy) = managers[0].clients().next().unwrap();
+                proxy.last_received == now && managers[1].last_received() == Some(now)
+            });
+            assert_eq!(events, [[], []]);
+        }
+
+        assert_eq!(client.connection_state(), ConnectionState::Connected);
+        assert_eq!(server.client_state(client_addr), ConnectionState::Connected);
+    }
+
+    #[test]
+    fn heartbeats_are_only_sent_when_nothing_else_was() {
+        let clock = ManualClock::new();
+        let mut server = server()
+            .with_clock(clock.clone())
+            .with_heartbeat_interval(Duration::from_secs(1));
+        let mut raw = UdpTransport::bind("127.0.0.1:0").unwrap();
+
+        let mut hello = PacketBuilder::new(PacketType::Hello);
+        "Tom".write_byte(&mut hello.stream(&mut ())).unwrap();
+        raw.send(server.local_addr().unwrap(), &hello.finish())
+            .unwrap();
+        tick_until(&mut [&mut server], |managers, _| {
+            managers[0].clients().count() == 1
+        });
+        assert_eq!(packet_type(&receive(&mut raw, 1)[0]), PacketType::Welcome);
+
+        clock.advance(Duration::from_millis(999));
+        server.update().unwrap();
+        clock.advance(Duration::from_millis(1));
+        server.replicate_world_state(&[]).unwrap();
+        server.update().unwrap();
+        clock.advance(Duration::from_millis(999));
+        server.update().unwrap();
+
+        let packets = receive(&mut raw, 1);
+        assert_eq!(packet_type(&packets[0]), PacketType::ReplicationData);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(raw.receive_packets(Instant::now()).unwrap().is_empty());
+
+        clock.advance(Duration::from_millis(1));
+        server.update().unwrap();
+        let packets = receive(&mut raw, 1);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packet_type(&packets[0]), PacketType::Heartbeat);
+    }
+
+    #[test]
+    fn silent_clients_time_out_on_the_server() {
+        let clock = ManualClock::new();
+        let mut server = server()
+            .with_clock(clock.clone())
+            .with_timeout(Duration::from_secs(3));
+        let mut client = client(&server, "Tom").with_clock(clock.clone());
+        connect(&mut server, &mut client);
+
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        server.replicate_world_state(&[cat]).unwrap();
+
+        clock.advance(Duration::from_millis(2999));
+        assert_eq!(server.update().unwrap(), []);
+        assert_eq!(server.clients().count(), 1);
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(
+            server.update().unwrap(),
+            [NetworkEvent::ClientTimedOut {
+                addr: client.local_addr().unwrap(),
+                player_id: client.player_id().unwrap(),
+            }]
+        );
+        assert_eq!(server.clients().count(), 0);
+        assert_eq!(server.peers().count(), 0);
+    }
+
+    #[test]
+    fn clients_time_out_and_drop_their_objects_when_the_server_goes_quiet() {
+        let clock = ManualClock::new();
+        let mut server = server().with_clock(clock.clone());
+        let mut client = client(&server, "Tom")
+            .with_clock(clock.clone())
+            .with_timeout(Duration::from_secs(3));
+        connect(&mut server, &mut client);
+
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        server.replicate_world_state(&[cat]).unwrap();
+        tick_until(&mut [&mut client], |managers, _| {
+            managers[0]
+                .linking_context()
+                .unwrap()
+                .get_game_object(0)
+                .is_some()
+        });
+
+        clock.advance(Duration::from_millis(2999));
+        assert_eq!(client.update().unwrap(), []);
+        assert_eq!(client.connection_state(), ConnectionState::Connected);
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(client.update().unwrap(), [NetworkEvent::TimedOut]);
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+        assert_eq!(client.player_id(), None);
+        assert_eq!(client.peers().count(), 0);
+        assert!(
+            client
+                .linking_context()
+                .unwrap()
+                .get_game_object(0)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn connecting_clients_give_up_after_the_timeout() {
+        let clock = ManualClock::new();
+        let mut nowhere = UdpTransport::bind("127.0.0.1:0").unwrap();
+        let mut client = NetworkManager::client(
+            "127.0.0.1:0",
+            nowhere.local_addr().unwrap(),
+            "Tom",
+            ObjectRegistry::default(),
+        )
+        .unwrap()
+        .with_clock(clock.clone())
+        .with_timeout(Duration::from_secs(3));
+
+        assert_eq!(client.update().unwrap(), []);
+        assert_eq!(packet_type(&receive(&mut nowhere, 1)[0]), PacketType::Hello);
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(client.update().unwrap(), [NetworkEvent::TimedOut]);
+        assert_eq!(client.connection_state(), ConnectionState::Disconnected);
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(client.update().unwrap(), []);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(nowhere.receive_packets(Instant::now()).unwrap().is_empty());
+    }
+}
@@ -1,2 +1,5 @@
 pub mod io;
+pub mod manager;
 pub mod network;
+pub mod packet;
+pub mod transport;
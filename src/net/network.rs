@@ -1,41 +1,130 @@
 use std::{
     collections::{HashMap, HashSet},
-    sync::{Arc, Mutex},
+    fmt::Debug,
+    sync::Arc,
 };
 
 use crate::{
-    GameObject,
-    io::bytes::{ReadStream, Readable, Writable},
-    linking_context::LinkingContext,
-    reflect::Reflect,
+    GameObject, Replicated,
+    io::{
+        bits::{BitReadable, BitWritable, ReadBitStream, WriteBitStream},
+        bytes::{ReadStream, Readable, Writable, WriteStream},
+    },
+    linking_context::{LinkingContext, read_game_object, read_reflected_fields, write_game_object},
+    reflect::{Reflect, UserDefinedType},
 };
 
-use super::io::{GameIoError, InputMemoryStream, OutputMemoryStream};
+use super::{
+    io::{GameIoError, InputMemoryStream, OutputMemoryStream},
+    packet::PacketHeader,
+};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum PacketType {
     Hello,
     ReplicationData,
     Disconnect,
+    /// The server's answer to a [`PacketType::Hello`], carrying the player id it assigned.
+    Welcome,
+    /// Sent by either side when it has had nothing else to send for a while.
+    Heartbeat,
+}
+
+impl TryFrom<u8> for PacketType {
+    type Error = GameIoError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PacketType::Hello),
+            1 => Ok(PacketType::ReplicationData),
+            2 => Ok(PacketType::Disconnect),
+            3 => Ok(PacketType::Welcome),
+            4 => Ok(PacketType::Heartbeat),
+            v => Err(GameIoError::InvalidEnumDiscriminant("PacketType", v)),
+        }
+    }
+}
+
+impl<W: WriteStream> Writable<W> for PacketType {
+    fn write_byte(&self, stream: &mut W) -> Result<(), W::Error> {
+        stream.write_u8(*self as u8)
+    }
 }
 
+impl<T> Readable<InputMemoryStream<'_, '_, T>> for PacketType {
+    fn read_byte(stream: &mut InputMemoryStream<'_, '_, T>) -> Result<Self, GameIoError> {
+        stream.read_u8()?.try_into()
+    }
+}
+
+/// Packet types are sent as a whole byte even when bit-packed, leaving room for new ones.
+impl<W: WriteBitStream> BitWritable<W> for PacketType {
+    fn write_bits(&self, stream: &mut W, _bits: usize) -> Result<(), W::Error> {
+        stream.write_u8_bits(*self as u8, 8)
+    }
+}
+
+impl<T> BitReadable<InputMemoryStream<'_, '_, T>> for PacketType {
+    fn read_bits(
+        stream: &mut InputMemoryStream<'_, '_, T>,
+        _bits: usize,
+    ) -> Result<Self, GameIoError> {
+        stream.read_u8_bits(8)?.try_into()
+    }
+}
+
+struct Fabric {
+    create: Box<dyn Fn() -> Arc<dyn GameObject>>,
+    skip_update: SkipUpdate,
+}
+
+type SkipUpdate = fn(&mut InputMemoryStream<'_, '_, LinkingContext>) -> Result<(), GameIoError>;
+
+#[derive(Default)]
 pub struct ObjectRegistry {
-    fabrics: HashMap<u32, Box<dyn Fn() -> Arc<dyn GameObject>>>,
+    fabrics: HashMap<u32, Fabric>,
 }
 
 impl ObjectRegistry {
-    pub fn register<T: Reflect + GameObject + 'static>(&mut self) -> &mut Self {
-        self.fabrics
-            .insert(T::type_id(), Box::new(|| Arc::new(T::create_instance())));
+    pub fn register<T: Reflect + Sync + Send + Debug + 'static>(&mut self) -> &mut Self {
+        if let Err(err) = T::create_instance().reflect().validate_for::<T>() {
+            panic!("cannot register class {}: {err:?}", T::type_id());
+        }
+
+        self.fabrics.insert(
+            T::type_id(),
+            Fabric {
+                create: Box::new(|| Arc::new(Replicated::new(T::create_instance()))),
+                skip_update: skip_dirty_state::<T>,
+            },
+        );
         self
     }
 
-    pub fn create_game_object(&self, type_id: u32) -> Arc<dyn GameObject> {
-        self.fabrics.get(&type_id).unwrap()()
+    pub fn create_game_object(&self, type_id: u32) -> Result<Arc<dyn GameObject>, GameIoError> {
+        self.fabrics
+            .get(&type_id)
+            .map(|fabric| (fabric.create)())
+            .ok_or(GameIoError::UnregisteredClass(type_id))
+    }
+
+    /// Reads past an update of an object of class `type_id` without creating one.
+    fn skip_update(
+        &self,
+        type_id: u32,
+        stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ) -> Result<(), GameIoError> {
+        let fabric = self
+            .fabrics
+            .get(&type_id)
+            .ok_or(GameIoError::UnregisteredClass(type_id))?;
+
+        (fabric.skip_update)(stream)
     }
 }
 
+#[derive(Default)]
 pub struct ReplicationManager {
     objects_to_me: HashSet<usize>,
 }
@@ -51,59 +140,63 @@ impl ReplicationManager {
         &self,
         stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
         go: &Arc<dyn GameObject>,
-    ) {
-        go.write_byte(stream).unwrap();
-        go.class_id().write_byte(stream).unwrap();
+    ) -> Result<(), GameIoError> {
+        let network_id = stream
+            .ctx
+            .get_network_id(go, true)
+            .ok_or(GameIoError::UnregisteredGameObject(0))?;
+        stream.write_var_usize(network_id)?;
+        go.class_id().write_byte(stream)?;
 
-        todo!(
-            /* Need add reflection methods to GameObject and write data from dyn data ptr */
-            /* Ugly architecture */
-            /* Need refactoring */
-        )
+        write_state(stream, go)
     }
 
+    /// Writes a whole [`PacketType::ReplicationData`] packet, header included.
     pub fn replicate_world_state(
         &self,
         stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
         gos: &[Arc<dyn GameObject>],
-    ) {
-        (PacketType::ReplicationData as u8)
-            .write_byte(stream)
-            .unwrap();
+    ) -> Result<(), GameIoError> {
+        PacketHeader::new(PacketType::ReplicationData).write_byte(stream)?;
         for go in gos {
-            self.replicate_into_stream(stream, go);
+            self.replicate_into_stream(stream, go)?;
         }
+
+        Ok(())
     }
 
     fn recv_replicated_object(
         &mut self,
         input: &mut InputMemoryStream<'_, '_, LinkingContext>,
         registry: &ObjectRegistry,
-    ) -> usize {
-        let go = Arc::<dyn GameObject>::read_byte(input);
-        let class_id = u32::read_byte(input).unwrap();
+    ) -> Result<usize, GameIoError> {
+        let network_id = input.read_var_usize()?;
+        let class_id = u32::read_byte(input)?;
 
-        match go {
-            Ok(go) => go.id(),
-            Err(super::io::GameIoError::UnregisteredGameObject(id)) => {
-                let go = registry.create_game_object(class_id);
-                input.ctx.insert_game_object(go.clone(), id);
+        let go = match input.ctx.get_game_object(network_id) {
+            Some(go) => go,
+            None => {
+                let go = registry.create_game_object(class_id)?;
+                input.ctx.insert_game_object(go.clone(), network_id);
 
-                go.id()
+                go
             }
-            _ => panic!(),
-        }
+        };
+
+        read_state(input, &go)?;
+
+        Ok(go.id())
     }
 
     pub fn recv_replicated_objects(
         &mut self,
         input: &mut InputMemoryStream<'_, '_, LinkingContext>,
         registry: &ObjectRegistry,
-    ) {
+    ) -> Result<(), GameIoError> {
         let mut set = HashSet::new();
 
         while input.remaining_bit_count() > 0 {
-            let go = self.recv_replicated_object(input, registry);
+            let go = self.recv_replicated_object(input, registry)?;
             set.insert(go);
         }
 
@@ -114,58 +207,164 @@ impl ReplicationManager {
         }
 
         self.objects_to_me = set;
+
+        Ok(())
     }
 
     pub fn replicate_create(
         &mut self,
         stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
         go: &Arc<dyn GameObject>,
-    ) {
+    ) -> Result<(), GameIoError> {
         let header = ReplicationHeader {
             action: ReplicationAction::Create,
-            network_id: stream.ctx.get_network_id(go, false).unwrap(),
+            network_id: stream
+                .ctx
+                .get_network_id(go, true)
+                .ok_or(GameIoError::UnregisteredGameObject(0))?,
             class_id: go.class_id(),
         };
-        header.write_byte(stream).unwrap();
-        /* todo() */
+        header.write_byte(stream)?;
+
+        write_state(stream, go)
     }
 
+    /// Sends only the fields in [`GameObject::dirty_state`]. Clearing the dirty state once
+    /// every peer has been updated is up to the caller.
     pub fn replicate_update(
         &mut self,
         stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
         go: &Arc<dyn GameObject>,
-    ) {
+    ) -> Result<(), GameIoError> {
         let header = ReplicationHeader {
             action: ReplicationAction::Update,
-            network_id: stream.ctx.get_network_id(go, false).unwrap(),
+            network_id: stream
+                .ctx
+                .get_network_id(go, false)
+                .ok_or(GameIoError::UnregisteredGameObject(0))?,
             class_id: go.class_id(),
         };
-        header.write_byte(stream).unwrap();
-        /* todo() */
+        header.write_byte(stream)?;
+
+        write_dirty_state(stream, go, go.dirty_state())
     }
 
     pub fn replicate_destroy(
         &mut self,
         stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
         go: &Arc<dyn GameObject>,
-    ) {
+    ) -> Result<(), GameIoError> {
         let header = ReplicationHeader {
             action: ReplicationAction::Destroy,
-            network_id: stream.ctx.get_network_id(go, false).unwrap(),
+            network_id: stream
+                .ctx
+                .get_network_id(go, false)
+                .ok_or(GameIoError::UnregisteredGameObject(0))?,
             class_id: go.class_id(),
         };
-        header.write_byte(stream).unwrap();
+        header.write_byte(stream)?;
+        stream.ctx.remove_game_object(go.id());
+
+        Ok(())
     }
 
-    fn process_replication_action(
+    pub fn process_replication_action(
         &mut self,
         stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
-    ) {
-        todo!()
+        registry: &ObjectRegistry,
+    ) -> Result<(), GameIoError> {
+        let header = ReplicationHeader::read_byte(stream)?;
+
+        match header.action {
+            ReplicationAction::Create => {
+                let go = match stream.ctx.get_game_object(header.network_id) {
+                    Some(go) => go,
+                    None => {
+                        let go = registry.create_game_object(header.class_id)?;
+                        stream.ctx.insert_game_object(go.clone(), header.network_id);
+
+                        go
+                    }
+                };
+
+                read_state(stream, &go)
+            }
+            ReplicationAction::Update => match stream.ctx.get_game_object(header.network_id) {
+                Some(go) => read_dirty_state(stream, &go),
+                None => registry.skip_update(header.class_id, stream),
+            },
+            ReplicationAction::Destroy => {
+                if let Some(go) = stream.ctx.get_game_object(header.network_id) {
+                    stream.ctx.remove_game_object(go.id());
+                    self.objects_to_me.remove(&go.id());
+                }
+
+                Ok(())
+            }
+        }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+fn dirty_state_bits(ty: &UserDefinedType) -> usize {
+    ty.fields.len().div_ceil(8) * 8
+}
+
+fn write_state(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    go: &Arc<dyn GameObject>,
+) -> Result<(), GameIoError> {
+    write_game_object(stream, go, go.type_info().all_fields_mask())
+}
+
+fn write_dirty_state(
+    stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
+    go: &Arc<dyn GameObject>,
+    dirty_state: u64,
+) -> Result<(), GameIoError> {
+    let ty = go.type_info();
+    let dirty_state = dirty_state & ty.all_fields_mask();
+    stream.write_u64_bits(dirty_state, dirty_state_bits(ty))?;
+
+    write_game_object(stream, go, dirty_state)
+}
+
+fn read_state(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    go: &Arc<dyn GameObject>,
+) -> Result<(), GameIoError> {
+    read_game_object(stream, go, go.type_info().all_fields_mask())
+}
+
+fn read_dirty_mask(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    ty: &UserDefinedType,
+) -> Result<u64, GameIoError> {
+    Ok(stream.read_u64_bits(dirty_state_bits(ty))? & ty.all_fields_mask())
+}
+
+fn read_dirty_state(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+    go: &Arc<dyn GameObject>,
+) -> Result<(), GameIoError> {
+    let dirty_state = read_dirty_mask(stream, go.type_info())?;
+
+    read_game_object(stream, go, dirty_state)
+}
+
+/// Reads an update into a scratch `T`. Nothing is registered and no reference is deferred, so
+/// the object the update was meant for leaves no trace.
+fn skip_dirty_state<T: Reflect + 'static>(
+    stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
+) -> Result<(), GameIoError> {
+    let mut scratch = T::create_instance();
+    let ty = scratch.reflect();
+    ty.validate_for::<T>()?;
+    let dirty_state = read_dirty_mask(stream, ty)?;
+
+    unsafe { read_reflected_fields(stream, ty, &mut scratch as *mut T as *mut u8, dirty_state) }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum ReplicationAction {
     Create,
@@ -173,6 +372,51 @@ pub enum ReplicationAction {
     Destroy,
 }
 
+impl ReplicationAction {
+    /// Width of an action when bit-packed.
+    pub const BITS: usize = 2;
+}
+
+impl TryFrom<u8> for ReplicationAction {
+    type Error = GameIoError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ReplicationAction::Create),
+            1 => Ok(ReplicationAction::Update),
+            2 => Ok(ReplicationAction::Destroy),
+            v => Err(GameIoError::InvalidEnumDiscriminant("ReplicationAction", v)),
+        }
+    }
+}
+
+impl<W: WriteStream> Writable<W> for ReplicationAction {
+    fn write_byte(&self, stream: &mut W) -> Result<(), W::Error> {
+        stream.write_u8(*self as u8)
+    }
+}
+
+impl<T> Readable<InputMemoryStream<'_, '_, T>> for ReplicationAction {
+    fn read_byte(stream: &mut InputMemoryStream<'_, '_, T>) -> Result<Self, GameIoError> {
+        stream.read_u8()?.try_into()
+    }
+}
+
+impl<W: WriteBitStream> BitWritable<W> for ReplicationAction {
+    fn write_bits(&self, stream: &mut W, _bits: usize) -> Result<(), W::Error> {
+        stream.write_u8_bits(*self as u8, Self::BITS)
+    }
+}
+
+impl<T> BitReadable<InputMemoryStream<'_, '_, T>> for ReplicationAction {
+    fn read_bits(
+        stream: &mut InputMemoryStream<'_, '_, T>,
+        _bits: usize,
+    ) -> Result<Self, GameIoError> {
+        stream.read_u8_bits(Self::BITS)?.try_into()
+    }
+}
+
 pub struct ReplicationHeader {
     pub action: ReplicationAction,
     pub network_id: usize,
@@ -184,8 +428,8 @@ impl Writable<OutputMemoryStream<'_, '_, LinkingContext>> for ReplicationHeader
         &self,
         stream: &mut OutputMemoryStream<'_, '_, LinkingContext>,
     ) -> Result<(), GameIoError> {
-        (self.action as u8).write_byte(stream)?;
-        self.network_id.write_byte(stream)?;
+        self.action.write_byte(stream)?;
+        stream.write_var_usize(self.network_id)?;
         self.class_id.write_byte(stream)?;
 
         Ok(())
@@ -197,9 +441,596 @@ impl Readable<InputMemoryStream<'_, '_, LinkingContext>> for ReplicationHeader {
         stream: &mut InputMemoryStream<'_, '_, LinkingContext>,
     ) -> Result<Self, GameIoError> {
         Ok(Self {
-            action: unsafe { std::mem::transmute(stream.read_u8()?) },
-            network_id: stream.read_usize()?,
+            action: ReplicationAction::read_byte(stream)?,
+            network_id: stream.read_var_usize()?,
             class_id: stream.read_u32()?,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Weak},
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        GameObject, Replicated,
+        io::{
+            bits::{BitReadable, BitWritable, WriteBitStream},
+            bytes::{Readable, Writable},
+        },
+        linking_context::LinkingContext,
+        member_field,
+        net::io::{GameIoError, InputMemoryStream, OutputMemoryStream},
+        reflect::{Reflect, Ty, UserDefinedType},
+    };
+
+    use super::{ObjectRegistry, PacketHeader, PacketType, ReplicationAction, ReplicationManager};
+
+    #[derive(Debug, Default, Clone, PartialEq)]
+    struct Cat {
+        health: u32,
+        speed: f32,
+        name: String,
+    }
+
+    impl Reflect for Cat {
+        fn reflect(&self) -> &'static UserDefinedType {
+            const INFO: &UserDefinedType = &UserDefinedType::new::<Cat>(&[
+                member_field!(Cat, health, Ty::U32),
+                member_field!(Cat, speed, Ty::F32),
+                member_field!(Cat, name, Ty::String),
+            ]);
+
+            INFO
+        }
+
+        fn type_id() -> u32 {
+            1
+        }
+
+        fn create_instance() -> Self {
+            Default::default()
+        }
+    }
+
+    #[derive(Debug, Default, Clone, Reflect)]
+    #[reflect(type_id = 2)]
+    struct Mouse {
+        hunter: Option<Weak<dyn GameObject>>,
+        owner: Option<Arc<dyn GameObject>>,
+    }
+
+    #[derive(Debug, Default, Clone, Reflect)]
+    #[reflect(type_id = 3)]
+    struct Pack {
+        members: Vec<Option<Weak<dyn GameObject>>>,
+    }
+
+    fn read_data<T: Clone + Default>(go: &Arc<dyn GameObject>) -> T {
+        let mut value = T::default();
+        go.with_data(&mut |data| {
+            value = unsafe { &*(data as *const T) }.clone();
+            Ok(())
+        })
+        .unwrap();
+
+        value
+    }
+
+    fn read_cat(go: &Arc<dyn GameObject>) -> Cat {
+        read_data(go)
+    }
+
+    fn process_all(
+        manager: &mut ReplicationManager,
+        buf: &[u8],
+        ctx: &mut LinkingContext,
+        registry: &ObjectRegistry,
+    ) -> Result<(), GameIoError> {
+        let mut input = InputMemoryStream::new(buf, ctx);
+        while input.remaining_bit_count() > 0 {
+            manager.process_replication_action(&mut input, registry)?;
+        }
+
+        Ok(())
+    }
+
+    fn recv_world_state(
+        manager: &mut ReplicationManager,
+        buf: &[u8],
+        ctx: &mut LinkingContext,
+        registry: &ObjectRegistry,
+    ) -> Result<(), GameIoError> {
+        let mut input = InputMemoryStream::new(buf, ctx);
+        PacketHeader::read_byte(&mut input)?;
+
+        manager.recv_replicated_objects(&mut input, registry)
+    }
+
+    #[test]
+    fn create_update_destroy_round_trip() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>();
+
+        let tom = Arc::new(Replicated::new(Cat {
+            health: 9,
+            speed: 1.5,
+            name: "Tom".to_string(),
+        }));
+        let felix = Arc::new(Replicated::new(Cat {
+            health: 7,
+            speed: 0.5,
+            name: "Felix".to_string(),
+        }));
+        let tom_go: Arc<dyn GameObject> = tom.clone();
+        let felix_go: Arc<dyn GameObject> = felix.clone();
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_create(&mut output, &tom_go).unwrap();
+        server.replicate_create(&mut output, &felix_go).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+
+        let tom_id = server_ctx.get_network_id(&tom_go, false).unwrap();
+        let felix_id = server_ctx.get_network_id(&felix_go, false).unwrap();
+        assert_eq!(
+            read_cat(&client_ctx.get_game_object(tom_id).unwrap()),
+            *tom.read()
+        );
+        assert_eq!(
+            read_cat(&client_ctx.get_game_object(felix_id).unwrap()),
+            *felix.read()
+        );
+
+        tom.write().health = 3;
+        tom.write().name = "Thomas".to_string();
+        tom.mark_dirty(0);
+        tom.mark_dirty(2);
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_update(&mut output, &tom_go).unwrap();
+        server.replicate_destroy(&mut output, &felix_go).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+
+        let client_tom = read_cat(&client_ctx.get_game_object(tom_id).unwrap());
+        assert_eq!(client_tom.health, 3);
+        assert_eq!(client_tom.name, "Thomas");
+        assert!(client_ctx.get_game_object(felix_id).is_none());
+        assert!(server_ctx.get_network_id(&felix_go, false).is_none());
+    }
+
+    #[test]
+    fn update_sends_only_dirty_fields() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>();
+
+        let cat = Arc::new(Replicated::new(Cat {
+            health: 9,
+            speed: 1.5,
+            name: "Tom".to_string(),
+        }));
+        let go: Arc<dyn GameObject> = cat.clone();
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_create(&mut output, &go).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+        let id = server_ctx.get_network_id(&go, false).unwrap();
+
+        {
+            let mut data = cat.write();
+            data.health = 1;
+            data.speed = 4.0;
+            data.name = "Not sent".to_string();
+        }
+        cat.mark_dirty(Cat::default().reflect().field_index("speed").unwrap());
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_update(&mut output, &go).unwrap();
+        let mut full = vec![];
+        let mut output = OutputMemoryStream::new(&mut full, &mut server_ctx);
+        server.replicate_create(&mut output, &go).unwrap();
+        assert!(buf.len() < full.len());
+
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+        go.clear_dirty_state();
+
+        let client_cat = read_cat(&client_ctx.get_game_object(id).unwrap());
+        assert_eq!(client_cat.health, 9);
+        assert_eq!(client_cat.speed, 4.0);
+        assert_eq!(client_cat.name, "Tom");
+        assert_eq!(go.dirty_state(), 0);
+    }
+
+    #[test]
+    fn object_refs_resolve_when_target_arrives_later() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>().register::<Mouse>();
+
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        let mouse: Arc<dyn GameObject> = Arc::new(Replicated::new(Mouse {
+            hunter: Some(Arc::downgrade(&cat)),
+            owner: Some(cat.clone()),
+        }));
+        let cat_id = server_ctx.get_network_id(&cat, true).unwrap();
+        let mouse_id = server_ctx.get_network_id(&mouse, true).unwrap();
+
+        let mut mouse_packet = vec![];
+        let mut output = OutputMemoryStream::new(&mut mouse_packet, &mut server_ctx);
+        server.replicate_create(&mut output, &mouse).unwrap();
+        let mut cat_packet = vec![];
+        let mut output = OutputMemoryStream::new(&mut cat_packet, &mut server_ctx);
+        server.replicate_create(&mut output, &cat).unwrap();
+
+        process_all(&mut client, &mouse_packet, &mut client_ctx, &registry).unwrap();
+        let client_mouse = client_ctx.get_game_object(mouse_id).unwrap();
+        let data: Mouse = read_data(&client_mouse);
+        assert!(data.hunter.is_none());
+        assert!(data.owner.is_none());
+        assert_eq!(client_ctx.unresolved_ref_count(), 2);
+
+        process_all(&mut client, &cat_packet, &mut client_ctx, &registry).unwrap();
+        let client_cat = client_ctx.get_game_object(cat_id).unwrap();
+        let data: Mouse = read_data(&client_mouse);
+        assert!(Arc::ptr_eq(
+            &data.hunter.unwrap().upgrade().unwrap(),
+            &client_cat
+        ));
+        assert!(Arc::ptr_eq(&data.owner.unwrap(), &client_cat));
+        assert_eq!(client_ctx.unresolved_ref_count(), 0);
+    }
+
+    #[test]
+    fn overwritten_object_ref_is_not_patched() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>().register::<Mouse>();
+
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        let mouse = Arc::new(Replicated::new(Mouse {
+            hunter: None,
+            owner: Some(cat.clone()),
+        }));
+        let mouse_go: Arc<dyn GameObject> = mouse.clone();
+        server_ctx.get_network_id(&cat, true).unwrap();
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_create(&mut output, &mouse_go).unwrap();
+        mouse.write().owner = None;
+        mouse.mark_dirty(1);
+        server.replicate_update(&mut output, &mouse_go).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+        assert_eq!(client_ctx.unresolved_ref_count(), 0);
+    }
+
+    #[test]
+    fn retargeted_object_ref_waits_for_the_new_target() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>().register::<Mouse>();
+
+        let tom: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        let felix: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        let mouse = Arc::new(Replicated::new(Mouse {
+            hunter: None,
+            owner: Some(tom.clone()),
+        }));
+        let mouse_go: Arc<dyn GameObject> = mouse.clone();
+        server_ctx.get_network_id(&tom, true).unwrap();
+        let felix_id = server_ctx.get_network_id(&felix, true).unwrap();
+        let mouse_id = server_ctx.get_network_id(&mouse_go, true).unwrap();
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_create(&mut output, &mouse_go).unwrap();
+        mouse.write().owner = Some(felix.clone());
+        mouse.mark_dirty(1);
+        server.replicate_update(&mut output, &mouse_go).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+        assert_eq!(client_ctx.unresolved_ref_count(), 1);
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_create(&mut output, &tom).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+        let client_mouse = client_ctx.get_game_object(mouse_id).unwrap();
+        assert!(read_data::<Mouse>(&client_mouse).owner.is_none());
+        assert_eq!(client_ctx.unresolved_ref_count(), 1);
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_create(&mut output, &felix).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+        let client_felix = client_ctx.get_game_object(felix_id).unwrap();
+        let owner = read_data::<Mouse>(&client_mouse).owner.unwrap();
+        assert!(Arc::ptr_eq(&owner, &client_felix));
+        assert_eq!(client_ctx.unresolved_ref_count(), 0);
+    }
+
+    #[test]
+    fn object_refs_inside_containers_resolve() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>().register::<Pack>();
+
+        let tom: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        let felix: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        let pack: Arc<dyn GameObject> = Arc::new(Replicated::new(Pack {
+            members: vec![
+                Some(Arc::downgrade(&tom)),
+                None,
+                Some(Arc::downgrade(&felix)),
+            ],
+        }));
+
+        let tom_id = server_ctx.get_network_id(&tom, true).unwrap();
+        let felix_id = server_ctx.get_network_id(&felix, true).unwrap();
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_create(&mut output, &pack).unwrap();
+        server.replicate_create(&mut output, &tom).unwrap();
+        server.replicate_create(&mut output, &felix).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+        assert_eq!(client_ctx.unresolved_ref_count(), 0);
+
+        let pack_id = server_ctx.get_network_id(&pack, false).unwrap();
+        let data: Pack = read_data(&client_ctx.get_game_object(pack_id).unwrap());
+        let members: Vec<_> = data
+            .members
+            .iter()
+            .map(|member| member.as_ref().and_then(Weak::upgrade))
+            .collect();
+
+        assert_eq!(members.len(), 3);
+        assert!(Arc::ptr_eq(
+            members[0].as_ref().unwrap(),
+            &client_ctx.get_game_object(tom_id).unwrap()
+        ));
+        assert!(members[1].is_none());
+        assert!(Arc::ptr_eq(
+            members[2].as_ref().unwrap(),
+            &client_ctx.get_game_object(felix_id).unwrap()
+        ));
+    }
+
+    #[test]
+    fn unresolved_object_refs_expire() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>().register::<Mouse>();
+
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        let mouse: Arc<dyn GameObject> = Arc::new(Replicated::new(Mouse {
+            hunter: None,
+            owner: Some(cat.clone()),
+        }));
+        let cat_id = server_ctx.get_network_id(&cat, true).unwrap();
+        let mouse_id = server_ctx.get_network_id(&mouse, true).unwrap();
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_create(&mut output, &mouse).unwrap();
+        let now = Instant::now();
+        client_ctx.set_now(now);
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+
+        let timeout = Duration::from_secs(5);
+        let almost = now + timeout - Duration::from_millis(1);
+        assert!(client_ctx.take_expired_refs(almost, timeout).is_empty());
+
+        let expired = client_ctx.take_expired_refs(now + timeout, timeout);
+        let client_mouse = client_ctx.get_game_object(mouse_id).unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].owner, Some(client_mouse.id()));
+        assert_eq!(expired[0].network_id, cat_id);
+        assert_eq!(expired[0].age, timeout);
+        assert_eq!(client_ctx.unresolved_ref_count(), 0);
+    }
+
+    #[test]
+    fn destroyed_objects_are_forgotten_by_the_next_world_state() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>();
+
+        let tom: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server
+            .replicate_world_state(&mut output, std::slice::from_ref(&tom))
+            .unwrap();
+        recv_world_state(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+        let tom_id = server_ctx.get_network_id(&tom, false).unwrap();
+        assert!(client_ctx.get_game_object(tom_id).is_some());
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_destroy(&mut output, &tom).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+        assert!(client_ctx.get_game_object(tom_id).is_none());
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_world_state(&mut output, &[]).unwrap();
+        recv_world_state(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+    }
+
+    #[test]
+    fn update_of_unknown_object_is_skipped() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+        let mut registry = ObjectRegistry::default();
+        registry.register::<Cat>().register::<Mouse>();
+
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+        let mouse = Arc::new(Replicated::new(Mouse {
+            hunter: Some(Arc::downgrade(&cat)),
+            owner: Some(cat.clone()),
+        }));
+        let mouse_go: Arc<dyn GameObject> = mouse.clone();
+        server_ctx.get_network_id(&cat, true).unwrap();
+        let mouse_id = server_ctx.get_network_id(&mouse_go, true).unwrap();
+        mouse.mark_dirty(0);
+        mouse.mark_dirty(1);
+
+        let tom: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat {
+            name: "Tom".to_string(),
+            ..Default::default()
+        }));
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_update(&mut output, &mouse_go).unwrap();
+        server.replicate_create(&mut output, &tom).unwrap();
+        process_all(&mut client, &buf, &mut client_ctx, &registry).unwrap();
+
+        assert!(client_ctx.get_game_object(mouse_id).is_none());
+        assert_eq!(client_ctx.unresolved_ref_count(), 0);
+
+        let tom_id = server_ctx.get_network_id(&tom, false).unwrap();
+        let client_tom = read_cat(&client_ctx.get_game_object(tom_id).unwrap());
+        assert_eq!(client_tom.name, "Tom");
+    }
+
+    #[test]
+    fn create_of_unregistered_class_is_rejected() {
+        let mut server_ctx = LinkingContext::default();
+        let mut client_ctx = LinkingContext::default();
+        let mut server = ReplicationManager::new();
+        let mut client = ReplicationManager::new();
+
+        let cat: Arc<dyn GameObject> = Arc::new(Replicated::new(Cat::default()));
+
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut server_ctx);
+        server.replicate_create(&mut output, &cat).unwrap();
+
+        let result = process_all(
+            &mut client,
+            &buf,
+            &mut client_ctx,
+            &ObjectRegistry::default(),
+        );
+        assert!(matches!(result, Err(GameIoError::UnregisteredClass(1))));
+    }
+
+    #[test]
+    fn unknown_replication_action_is_rejected() {
+        let mut ctx = LinkingContext::default();
+        let registry = ObjectRegistry::default();
+
+        for action in [3, 0x7F, 0xFF] {
+            let result = process_all(
+                &mut ReplicationManager::new(),
+                &[action, 0, 1, 0, 0, 0],
+                &mut ctx,
+                &registry,
+            );
+            assert!(matches!(
+                result,
+                Err(GameIoError::InvalidEnumDiscriminant("ReplicationAction", v)) if v == action
+            ));
+        }
+    }
+
+    #[test]
+    fn enums_round_trip_through_bytes_and_bits() {
+        let actions = [
+            ReplicationAction::Create,
+            ReplicationAction::Update,
+            ReplicationAction::Destroy,
+        ];
+        let packets = [
+            PacketType::Hello,
+            PacketType::ReplicationData,
+            PacketType::Disconnect,
+            PacketType::Welcome,
+            PacketType::Heartbeat,
+        ];
+
+        let mut buf = vec![];
+        let mut ctx = ();
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        for action in actions {
+            action.write_byte(&mut output).unwrap();
+            action.write_bits(&mut output, 0).unwrap();
+        }
+        for packet in packets {
+            packet.write_byte(&mut output).unwrap();
+            packet.write_bits(&mut output, 0).unwrap();
+        }
+
+        assert_eq!(buf.len() * 8, 3 * (8 + 2) + 5 * (8 + 8) + 2);
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        for action in actions {
+            assert_eq!(ReplicationAction::read_byte(&mut input).unwrap(), action);
+            assert_eq!(ReplicationAction::read_bits(&mut input, 0).unwrap(), action);
+            assert_eq!(ReplicationAction::try_from(action as u8).unwrap(), action);
+        }
+        for packet in packets {
+            assert_eq!(PacketType::read_byte(&mut input).unwrap(), packet);
+            assert_eq!(PacketType::read_bits(&mut input, 0).unwrap(), packet);
+            assert_eq!(PacketType::try_from(packet as u8).unwrap(), packet);
+        }
+    }
+
+    #[test]
+    fn unknown_enum_discriminants_are_rejected() {
+        assert!(matches!(
+            PacketType::try_from(5),
+            Err(GameIoError::InvalidEnumDiscriminant("PacketType", 5))
+        ));
+        assert!(matches!(
+            PacketType::read_byte(&mut InputMemoryStream::new(&[0xFF], &mut ())),
+            Err(GameIoError::InvalidEnumDiscriminant("PacketType", 0xFF))
+        ));
+
+        let mut buf = vec![];
+        let mut ctx = ();
+        OutputMemoryStream::new(&mut buf, &mut ctx)
+            .write_u8_bits(3, 2)
+            .unwrap();
+        assert!(matches!(
+            ReplicationAction::read_bits(&mut InputMemoryStream::new(&buf, &mut ctx), 0),
+            Err(GameIoError::InvalidEnumDiscriminant("ReplicationAction", 3))
+        ));
+    }
+}
@@ -0,0 +1,260 @@
+use std::{collections::HashMap, net::SocketAddr};
+
+use crate::io::bytes::{ReadStream, Readable, Writable, WriteStream};
+
+use super::{
+    io::{GameIoError, InputMemoryStream, OutputMemoryStream},
+    network::PacketType,
+};
+
+/// First bytes of every packet, so that stray datagrams from other programs are dropped before
+/// anything else is decoded.
+pub const PROTOCOL_ID: u32 = u32::from_le_bytes(*b"PHAE");
+
+/// Bumped whenever the wire format changes. Peers only talk to the exact same version.
+pub const PROTOCOL_VERSION: u16 = 3;
+
+/// Starts every packet: [`PROTOCOL_ID`], [`PROTOCOL_VERSION`] and the [`PacketType`] of the
+/// payload that follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PacketHeader {
+    pub protocol_id: u32,
+    pub version: u16,
+    pub packet_type: PacketType,
+}
+
+impl PacketHeader {
+    pub fn new(packet_type: PacketType) -> Self {
+        Self {
+            protocol_id: PROTOCOL_ID,
+            version: PROTOCOL_VERSION,
+            packet_type,
+        }
+    }
+}
+
+impl<W: WriteStream> Writable<W> for PacketHeader {
+    fn write_byte(&self, stream: &mut W) -> Result<(), W::Error> {
+        stream.write_u32(self.protocol_id)?;
+        stream.write_u16(self.version)?;
+        self.packet_type.write_byte(stream)
+    }
+}
+
+/// Fails on a foreign protocol id or another version without reading any further.
+impl<T> Readable<InputMemoryStream<'_, '_, T>> for PacketHeader {
+    fn read_byte(stream: &mut InputMemoryStream<'_, '_, T>) -> Result<Self, GameIoError> {
+        let protocol_id = stream.read_u32()?;
+        if protocol_id != PROTOCOL_ID {
+            return Err(GameIoError::ForeignProtocol(protocol_id));
+        }
+
+        let version = stream.read_u16()?;
+        if version != PROTOCOL_VERSION {
+            return Err(GameIoError::ProtocolVersionMismatch(version));
+        }
+
+        Ok(Self {
+            protocol_id,
+            version,
+            packet_type: PacketType::read_byte(stream)?,
+        })
+    }
+}
+
+/// Builds a packet buffer that starts with a [`PacketHeader`].
+pub struct PacketBuilder {
+    buffer: Vec<u8>,
+}
+
+impl PacketBuilder {
+    pub fn new(packet_type: PacketType) -> Self {
+        let mut buffer = vec![];
+        PacketHeader::new(packet_type)
+            .write_byte(&mut OutputMemoryStream::new(&mut buffer, &mut ()))
+            .expect("writing to a memory stream only fails on out of memory");
+
+        Self { buffer }
+    }
+
+    /// A stream appending to the payload. Each stream starts on a fresh byte, so bits written
+    /// after the last whole byte of a previous stream are kept but padded.
+    pub fn stream<'a, T>(&'a mut self, ctx: &'a mut T) -> OutputMemoryStream<'a, 'a, T> {
+        OutputMemoryStream::new(&mut self.buffer, ctx)
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+type Handler<S, T> = Box<
+    dyn FnMut(&mut S, SocketAddr, &mut InputMemoryStream<'_, '_, T>) -> Result<(), GameIoError>,
+>;
+
+/// Reads the [`PacketHeader`] of incoming packets and hands the payload to the handler
+/// registered for its [`PacketType`]. Handlers get the `S` passed to [`Self::dispatch`], which
+/// is where the game state they act on lives.
+pub struct PacketDispatcher<S, T> {
+    handlers: HashMap<PacketType, Handler<S, T>>,
+}
+
+impl<S, T> Default for PacketDispatcher<S, T> {
+    fn default() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+}
+
+impl<S, T> PacketDispatcher<S, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the handler for `packet_type`, if any.
+    pub fn register(
+        &mut self,
+        packet_type: PacketType,
+        handler: impl FnMut(
+            &mut S,
+            SocketAddr,
+            &mut InputMemoryStream<'_, '_, T>,
+        ) -> Result<(), GameIoError>
+        + 'static,
+    ) -> &mut Self {
+        self.handlers.insert(packet_type, Box::new(handler));
+        self
+    }
+
+    /// Routes the packet in `input`, sent by `from`, and returns its type. Packets that are not
+    /// ours, or that nobody handles, are rejected before their payload is touched.
+    pub fn dispatch(
+        &mut self,
+        state: &mut S,
+        from: SocketAddr,
+        input: &mut InputMemoryStream<'_, '_, T>,
+    ) -> Result<PacketType, GameIoError> {
+        let header = PacketHeader::read_byte(input)?;
+        let handler = self
+            .handlers
+            .get_mut(&header.packet_type)
+            .ok_or(GameIoError::UnhandledPacket(header.packet_type))?;
+
+        handler(state, from, input)?;
+
+        Ok(header.packet_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use crate::{
+        io::bytes::{Readable, Writable, WriteStream},
+        net::{
+            io::{GameIoError, InputMemoryStream},
+            network::PacketType,
+        },
+    };
+
+    use super::{PROTOCOL_ID, PROTOCOL_VERSION, PacketBuilder, PacketDispatcher};
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Received {
+        hellos: Vec<(SocketAddr, String)>,
+        disconnects: usize,
+    }
+
+    fn dispatcher() -> PacketDispatcher<Received, ()> {
+        let mut dispatcher = PacketDispatcher::new();
+        dispatcher
+            .register(PacketType::Hello, |state: &mut Received, from, input| {
+                let name = String::read_byte(input)?;
+                state.hellos.push((from, name));
+                Ok(())
+            })
+            .register(PacketType::Disconnect, |state, _, _| {
+                state.disconnects += 1;
+                Ok(())
+            });
+
+        dispatcher
+    }
+
+    fn addr() -> SocketAddr {
+        "127.0.0.1:4000".parse().unwrap()
+    }
+
+    fn dispatch(buf: &[u8]) -> (Result<PacketType, GameIoError>, Received) {
+        let mut received = Received::default();
+        let mut ctx = ();
+        let mut input = InputMemoryStream::new(buf, &mut ctx);
+        let result = dispatcher().dispatch(&mut received, addr(), &mut input);
+
+        (result, received)
+    }
+
+    #[test]
+    fn builder_writes_the_header_before_the_payload() {
+        let mut builder = PacketBuilder::new(PacketType::Hello);
+        builder.stream(&mut ()).write_u8(0xAB).unwrap();
+        builder.stream(&mut ()).write_u8(0xCD).unwrap();
+
+        let mut expected = PROTOCOL_ID.to_le_bytes().to_vec();
+        expected.extend(PROTOCOL_VERSION.to_le_bytes());
+        expected.extend([PacketType::Hello as u8, 0xAB, 0xCD]);
+        assert_eq!(builder.finish(), expected);
+    }
+
+    #[test]
+    fn packets_are_routed_by_type() {
+        let mut builder = PacketBuilder::new(PacketType::Hello);
+        "Tom".write_byte(&mut builder.stream(&mut ())).unwrap();
+        let (result, received) = dispatch(&builder.finish());
+
+        assert_eq!(result.unwrap(), PacketType::Hello);
+        assert_eq!(received.hellos, [(addr(), "Tom".to_string())]);
+        assert_eq!(received.disconnects, 0);
+
+        let (result, received) = dispatch(&PacketBuilder::new(PacketType::Disconnect).finish());
+        assert_eq!(result.unwrap(), PacketType::Disconnect);
+        assert_eq!(received.disconnects, 1);
+    }
+
+    #[test]
+    fn foreign_and_mismatched_packets_never_reach_a_handler() {
+        let packet = PacketBuilder::new(PacketType::Disconnect).finish();
+
+        let mut foreign = packet.clone();
+        foreign[0] ^= 0xFF;
+        let (result, received) = dispatch(&foreign);
+        assert!(matches!(result, Err(GameIoError::ForeignProtocol(id)) if id != PROTOCOL_ID));
+        assert_eq!(received, Received::default());
+
+        let mut mismatched = packet.clone();
+        mismatched[4..6].copy_from_slice(&(PROTOCOL_VERSION + 1).to_le_bytes());
+        let (result, received) = dispatch(&mismatched);
+        assert!(matches!(
+            result,
+            Err(GameIoError::ProtocolVersionMismatch(v)) if v == PROTOCOL_VERSION + 1
+        ));
+        assert_eq!(received, Received::default());
+
+        let (result, _) = dispatch(&packet[..3]);
+        assert!(matches!(result, Err(GameIoError::UnexpectedEof(32, 24))));
+    }
+
+    #[test]
+    fn packets_without_a_handler_are_rejected() {
+        let (result, received) =
+            dispatch(&PacketBuilder::new(PacketType::ReplicationData).finish());
+
+        assert!(matches!(
+            result,
+            Err(GameIoError::UnhandledPacket(PacketType::ReplicationData))
+        ));
+        assert_eq!(received, Received::default());
+    }
+}
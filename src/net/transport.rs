@@ -0,0 +1,393 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Instant,
+};
+
+use crate::io::bytes::Readable;
+
+use super::{io::InputMemoryStream, packet::PacketHeader};
+
+/// Fits in a 1500-byte Ethernet frame together with the IPv4 and UDP headers.
+pub const DEFAULT_MTU: usize = 1470;
+
+/// How many remote addresses a transport keeps [`Peer`] stats for.
+pub const DEFAULT_MAX_PEERS: usize = 1024;
+
+/// How many datagrams one [`UdpTransport::receive_packets`] call reads at most.
+pub const DEFAULT_MAX_PACKETS_PER_POLL: usize = 1024;
+
+#[derive(Debug)]
+pub enum TransportError {
+    Io(std::io::Error),
+    /// A packet of the first size that does not fit the MTU, the second.
+    PacketTooLarge(usize, usize),
+}
+
+impl From<std::io::Error> for TransportError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// A remote address this transport has sent packets to or received packets from.
+#[derive(Clone, Debug)]
+pub struct Peer {
+    pub addr: SocketAddr,
+    pub last_received: Option<Instant>,
+    pub packets_sent: u64,
+    pub packets_received: u64,
+}
+
+impl Peer {
+    fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            last_received: None,
+            packets_sent: 0,
+            packets_received: 0,
+        }
+    }
+}
+
+/// A datagram received from `from`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Packet {
+    pub from: SocketAddr,
+    pub data: Vec<u8>,
+}
+
+impl Packet {
+    pub fn stream<'ctx, 'buffer, T>(
+        &'buffer self,
+        ctx: &'ctx mut T,
+    ) -> InputMemoryStream<'ctx, 'buffer, T> {
+        InputMemoryStream::new(&self.data, ctx)
+    }
+}
+
+/// A non-blocking UDP socket that sends whole stream buffers as single datagrams. Nothing is
+/// ever split or reassembled, so every packet has to fit the MTU.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    mtu: usize,
+    peers: HashMap<SocketAddr, Peer>,
+    max_peers: usize,
+    max_packets_per_poll: usize,
+    recv_buffer: Vec<u8>,
+}
+
+impl UdpTransport {
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self, TransportError> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            mtu: DEFAULT_MTU,
+            peers: HashMap::new(),
+            max_peers: DEFAULT_MAX_PEERS,
+            max_packets_per_poll: DEFAULT_MAX_PACKETS_PER_POLL,
+            recv_buffer: vec![],
+        })
+    }
+
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Once `max_peers` addresses are tracked, a new one replaces the peer heard from least
+    /// recently.
+    pub fn with_max_peers(mut self, max_peers: usize) -> Self {
+        self.max_peers = max_peers;
+        self
+    }
+
+    /// Datagrams past `max_packets_per_poll` are left in the socket for the next call.
+    pub fn with_max_packets_per_poll(mut self, max_packets_per_poll: usize) -> Self {
+        self.max_packets_per_poll = max_packets_per_poll;
+        self
+    }
+
+    pub fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, TransportError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Sends `packet`, usually the buffer an [`OutputMemoryStream`] was written into, as one
+    /// datagram.
+    ///
+    /// [`OutputMemoryStream`]: super::io::OutputMemoryStream
+    pub fn send(&mut self, addr: SocketAddr, packet: &[u8]) -> Result<(), TransportError> {
+        if packet.len() > self.mtu {
+            return Err(TransportError::PacketTooLarge(packet.len(), self.mtu));
+        }
+
+        self.socket.send_to(packet, addr)?;
+        self.peer_mut(addr).packets_sent += 1;
+
+        Ok(())
+    }
+
+    /// Drains the datagrams that have arrived since the last call without blocking, up to the
+    /// per-poll limit. Datagrams larger than the MTU or without a valid [`PacketHeader`] are
+    /// dropped, and their senders are not recorded as peers. Senders of the others are marked as
+    /// last heard from at `now`.
+    pub fn receive_packets(&mut self, now: Instant) -> Result<Vec<Packet>, TransportError> {
+        // One spare byte tells a datagram that exactly fits apart from a truncated one.
+        self.recv_buffer.resize(self.mtu + 1, 0);
+        let mut packets = vec![];
+
+        for _ in 0..self.max_packets_per_poll {
+            let (len, from) = match self.socket.recv_from(&mut self.recv_buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                // An earlier send hit a closed port, which says nothing about this socket.
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => continue,
+                Err(err) => return Err(err.into()),
+            };
+
+            if len > self.mtu {
+                continue;
+            }
+
+            let data = &self.recv_buffer[..len];
+            if PacketHeader::read_byte(&mut InputMemoryStream::new(data, &mut ())).is_err() {
+                continue;
+            }
+            let data = data.to_vec();
+
+            let peer = self.peer_mut(from);
+            peer.last_received = Some(now);
+            peer.packets_received += 1;
+
+            packets.push(Packet { from, data });
+        }
+
+        Ok(packets)
+    }
+
+    pub fn peer(&self, addr: SocketAddr) -> Option<&Peer> {
+        self.peers.get(&addr)
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &Peer> {
+        self.peers.values()
+    }
+
+    pub fn remove_peer(&mut self, addr: SocketAddr) -> Option<Peer> {
+        self.peers.remove(&addr)
+    }
+
+    /// The peer for `addr`, making room for it if the table is full.
+    fn peer_mut(&mut self, addr: SocketAddr) -> &mut Peer {
+        if !self.peers.contains_key(&addr) && self.peers.len() >= self.max_peers {
+            let oldest = self
+                .peers
+                .values()
+                .min_by_key(|peer| peer.last_received)
+                .map(|peer| peer.addr);
+            if let Some(oldest) = oldest {
+                self.peers.remove(&oldest);
+            }
+        }
+
+        self.peers.entry(addr).or_insert_with(|| Peer::new(addr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        io::bytes::{ReadStream, Readable, WriteStream},
+        net::{
+            network::PacketType,
+            packet::{PacketBuilder, PacketHeader},
+        },
+    };
+
+    use super::{Packet, TransportError, UdpTransport};
+
+    fn loopback() -> UdpTransport {
+        UdpTransport::bind("127.0.0.1:0").unwrap()
+    }
+
+    fn heartbeat(payload: &[u8]) -> Vec<u8> {
+        let mut packet = PacketBuilder::new(PacketType::Heartbeat).finish();
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// Polls until `count` packets have arrived, as a game loop would over several ticks.
+    fn receive(transport: &mut UdpTransport, count: usize) -> Vec<Packet> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut packets = vec![];
+
+        while packets.len() < count && Instant::now() < deadline {
+            packets.extend(transport.receive_packets(Instant::now()).unwrap());
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        packets
+    }
+
+    #[test]
+    fn packets_arrive_whole_and_in_order_on_loopback() {
+        let mut server = loopback();
+        let mut client = loopback();
+        let client_addr = client.local_addr().unwrap();
+
+        for i in 0..3u32 {
+            let mut builder = PacketBuilder::new(PacketType::Heartbeat);
+            let mut ctx = ();
+            let mut output = builder.stream(&mut ctx);
+            output.write_u32(i).unwrap();
+            output.write_var_usize(1000).unwrap();
+            server.send(client_addr, &builder.finish()).unwrap();
+        }
+
+        let packets = receive(&mut client, 3);
+        assert_eq!(packets.len(), 3);
+
+        for (i, packet) in packets.iter().enumerate() {
+            assert_eq!(packet.from, server.local_addr().unwrap());
+
+            let mut ctx = ();
+            let mut input = packet.stream(&mut ctx);
+            PacketHeader::read_byte(&mut input).unwrap();
+            assert_eq!(input.read_u32().unwrap(), i as u32);
+            assert_eq!(input.read_var_usize().unwrap(), 1000);
+            assert_eq!(input.remaining_bit_count(), 0);
+        }
+    }
+
+    #[test]
+    fn polling_an_idle_socket_returns_immediately() {
+        let mut transport = loopback();
+        let started = Instant::now();
+
+        assert!(
+            transport
+                .receive_packets(Instant::now())
+                .unwrap()
+                .is_empty()
+        );
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn packets_over_the_mtu_are_not_sent_or_received() {
+        let mut server = loopback().with_mtu(64);
+        let mut client = loopback().with_mtu(32);
+        let client_addr = client.local_addr().unwrap();
+
+        assert!(matches!(
+            server.send(client_addr, &[0; 65]),
+            Err(TransportError::PacketTooLarge(65, 64))
+        ));
+
+        let header_len = heartbeat(&[]).len();
+        let too_large = heartbeat(&vec![1; 33 - header_len]);
+        let fits = heartbeat(&vec![2; 32 - header_len]);
+        server.send(client_addr, &too_large).unwrap();
+        server.send(client_addr, &fits).unwrap();
+
+        let packets = receive(&mut client, 1);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].data, fits);
+    }
+
+    #[test]
+    fn peers_are_tracked_per_address() {
+        let mut server = loopback();
+        let mut a = loopback();
+        let mut b = loopback();
+        let server_addr = server.local_addr().unwrap();
+
+        a.send(server_addr, &heartbeat(b"a")).unwrap();
+        b.send(server_addr, &heartbeat(b"b1")).unwrap();
+        b.send(server_addr, &heartbeat(b"b2")).unwrap();
+        assert_eq!(receive(&mut server, 3).len(), 3);
+
+        let a_addr = a.local_addr().unwrap();
+        let b_addr = b.local_addr().unwrap();
+        assert_eq!(server.peers().count(), 2);
+        assert_eq!(server.peer(a_addr).unwrap().packets_received, 1);
+        assert_eq!(server.peer(b_addr).unwrap().packets_received, 2);
+        assert!(server.peer(b_addr).unwrap().last_received.is_some());
+        assert_eq!(b.peer(server_addr).unwrap().packets_sent, 2);
+
+        assert!(server.remove_peer(a_addr).is_some());
+        assert!(server.peer(a_addr).is_none());
+        assert_eq!(server.peers().count(), 1);
+    }
+
+    #[test]
+    fn a_full_peer_table_evicts_the_peer_heard_from_least_recently() {
+        let mut server = loopback().with_max_peers(2);
+        let server_addr = server.local_addr().unwrap();
+        let mut senders = [loopback(), loopback(), loopback()];
+
+        let start = Instant::now();
+        for (i, sender) in (0..).zip(&mut senders) {
+            sender.send(server_addr, &heartbeat(&[])).unwrap();
+
+            let heard_at = start + Duration::from_secs(i);
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while server.receive_packets(heard_at).unwrap().is_empty() && Instant::now() < deadline
+            {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+
+        let addrs = senders.map(|sender| sender.local_addr().unwrap());
+        assert_eq!(server.peers().count(), 2);
+        assert!(server.peer(addrs[0]).is_none());
+        assert!(server.peer(addrs[1]).is_some());
+        assert!(server.peer(addrs[2]).is_some());
+    }
+
+    #[test]
+    fn a_poll_reads_at_most_the_packet_limit() {
+        let mut server = loopback();
+        let mut client = loopback().with_max_packets_per_poll(2);
+        let client_addr = client.local_addr().unwrap();
+
+        for i in 0..3 {
+            server.send(client_addr, &heartbeat(&[i])).unwrap();
+        }
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(client.receive_packets(Instant::now()).unwrap().len(), 2);
+        assert_eq!(receive(&mut client, 1).len(), 1);
+    }
+
+    #[test]
+    fn datagrams_without_a_header_do_not_make_peers() {
+        let mut server = loopback();
+        let mut stranger = loopback();
+        let server_addr = server.local_addr().unwrap();
+
+        stranger.send(server_addr, b"not a packet").unwrap();
+        stranger.send(server_addr, &heartbeat(&[])).unwrap();
+
+        let packets = receive(&mut server, 1);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].data, heartbeat(&[]));
+        assert_eq!(server.peers().count(), 1);
+        assert_eq!(
+            server
+                .peer(stranger.local_addr().unwrap())
+                .unwrap()
+                .packets_received,
+            1
+        );
+    }
+}
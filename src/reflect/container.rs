@@ -0,0 +1,182 @@
+use std::any::TypeId;
+
+use super::{ReflectTy, Ty, UserDefinedType};
+
+#[derive(Debug)]
+pub struct VecTy {
+    pub(crate) elem: Ty,
+    pub(crate) elem_type_id: fn() -> TypeId,
+    pub(crate) elem_size: usize,
+    pub(crate) elem_align: usize,
+    pub(crate) type_id: fn() -> TypeId,
+    pub(crate) size: usize,
+    pub(crate) align: usize,
+    pub(crate) len: unsafe fn(*const u8) -> usize,
+    pub(crate) as_ptr: unsafe fn(*const u8) -> *const u8,
+    pub(crate) as_mut_ptr: unsafe fn(*mut u8) -> *mut u8,
+    pub(crate) clear: unsafe fn(*mut u8),
+    /// Appends a default element and returns a pointer to it.
+    pub(crate) push_default: unsafe fn(*mut u8) -> *mut u8,
+}
+
+impl VecTy {
+    pub const fn of<T: ReflectTy + Default + 'static>() -> Self {
+        Self {
+            elem: T::TY,
+            elem_type_id: TypeId::of::<T>,
+            elem_size: size_of::<T>(),
+            elem_align: align_of::<T>(),
+            type_id: TypeId::of::<Vec<T>>,
+            size: size_of::<Vec<T>>(),
+            align: align_of::<Vec<T>>(),
+            len: vec_len::<T>,
+            as_ptr: vec_as_ptr::<T>,
+            as_mut_ptr: vec_as_mut_ptr::<T>,
+            clear: vec_clear::<T>,
+            push_default: vec_push_default::<T>,
+        }
+    }
+}
+
+unsafe fn vec_len<T>(data: *const u8) -> usize {
+    unsafe { (*(data as *const Vec<T>)).len() }
+}
+
+unsafe fn vec_as_ptr<T>(data: *const u8) -> *const u8 {
+    unsafe { (*(data as *const Vec<T>)).as_ptr() as *const u8 }
+}
+
+unsafe fn vec_as_mut_ptr<T>(data: *mut u8) -> *mut u8 {
+    unsafe { (*(data as *mut Vec<T>)).as_mut_ptr() as *mut u8 }
+}
+
+unsafe fn vec_clear<T>(data: *mut u8) {
+    unsafe { (*(data as *mut Vec<T>)).clear() }
+}
+
+unsafe fn vec_push_default<T: Default>(data: *mut u8) -> *mut u8 {
+    let vec = unsafe { &mut *(data as *mut Vec<T>) };
+    vec.push(T::default());
+
+    vec.last_mut().unwrap() as *mut T as *mut u8
+}
+
+// SAFETY: the container info is built from `T` itself, and `T::TY` describes `T`.
+unsafe impl<T: ReflectTy + Default + 'static> ReflectTy for Vec<T> {
+    const TY: Ty = Ty::Vec(&VecTy::of::<T>());
+}
+
+#[derive(Debug)]
+pub struct OptionTy {
+    pub(crate) elem: Ty,
+    pub(crate) elem_type_id: fn() -> TypeId,
+    pub(crate) elem_size: usize,
+    pub(crate) elem_align: usize,
+    pub(crate) type_id: fn() -> TypeId,
+    pub(crate) size: usize,
+    pub(crate) align: usize,
+    /// Returns a pointer to the contained value, or null for `None`.
+    pub(crate) get: unsafe fn(*const u8) -> *const u8,
+    pub(crate) get_mut: unsafe fn(*mut u8) -> *mut u8,
+    pub(crate) clear: unsafe fn(*mut u8),
+    /// Replaces the value with `Some(Default::default())` and returns a pointer to it.
+    pub(crate) insert_default: unsafe fn(*mut u8) -> *mut u8,
+}
+
+impl OptionTy {
+    pub const fn of<T: ReflectTy + Default + 'static>() -> Self {
+        Self {
+            elem: T::TY,
+            elem_type_id: TypeId::of::<T>,
+            elem_size: size_of::<T>(),
+            elem_align: align_of::<T>(),
+            type_id: TypeId::of::<Option<T>>,
+            size: size_of::<Option<T>>(),
+            align: align_of::<Option<T>>(),
+            get: option_get::<T>,
+            get_mut: option_get_mut::<T>,
+            clear: option_clear::<T>,
+            insert_default: option_insert_default::<T>,
+        }
+    }
+}
+
+unsafe fn option_get<T>(data: *const u8) -> *const u8 {
+    match unsafe { &*(data as *const Option<T>) } {
+        Some(v) => v as *const T as *const u8,
+        None => std::ptr::null(),
+    }
+}
+
+unsafe fn option_get_mut<T>(data: *mut u8) -> *mut u8 {
+    match unsafe { &mut *(data as *mut Option<T>) } {
+        Some(v) => v as *mut T as *mut u8,
+        None => std::ptr::null_mut(),
+    }
+}
+
+unsafe fn option_clear<T>(data: *mut u8) {
+    unsafe { *(data as *mut Option<T>) = None }
+}
+
+unsafe fn option_insert_default<T: Default>(data: *mut u8) -> *mut u8 {
+    let option = unsafe { &mut *(data as *mut Option<T>) };
+
+    option.insert(T::default()) as *mut T as *mut u8
+}
+
+// SAFETY: as for `Vec<T>`.
+unsafe impl<T: ReflectTy + Default + 'static> ReflectTy for Option<T> {
+    const TY: Ty = Ty::Option(&OptionTy::of::<T>());
+}
+
+#[derive(Debug)]
+pub struct ArrayTy {
+    pub(crate) elem: Ty,
+    pub(crate) elem_type_id: fn() -> TypeId,
+    pub(crate) elem_size: usize,
+    pub(crate) elem_align: usize,
+    pub(crate) len: usize,
+    pub(crate) type_id: fn() -> TypeId,
+    pub(crate) size: usize,
+    pub(crate) align: usize,
+}
+
+impl ArrayTy {
+    pub const fn of<T: ReflectTy + 'static, const N: usize>() -> Self {
+        Self {
+            elem: T::TY,
+            elem_type_id: TypeId::of::<T>,
+            elem_size: size_of::<T>(),
+            elem_align: align_of::<T>(),
+            len: N,
+            type_id: TypeId::of::<[T; N]>,
+            size: size_of::<[T; N]>(),
+            align: align_of::<[T; N]>(),
+        }
+    }
+}
+
+// SAFETY: as for `Vec<T>`.
+unsafe impl<T: ReflectTy + 'static, const N: usize> ReflectTy for [T; N] {
+    const TY: Ty = Ty::Array(&ArrayTy::of::<T, N>());
+}
+
+#[derive(Debug)]
+pub struct StructTy {
+    pub(crate) ty: &'static UserDefinedType,
+    pub(crate) type_id: fn() -> TypeId,
+    pub(crate) size: usize,
+    pub(crate) align: usize,
+}
+
+impl StructTy {
+    pub const fn of<T: 'static>(ty: &'static UserDefinedType) -> Self {
+        Self {
+            ty,
+            type_id: TypeId::of::<T>,
+            size: size_of::<T>(),
+            align: align_of::<T>(),
+        }
+    }
+}
@@ -1,31 +1,406 @@
-#[derive(Clone, Copy, Debug)]
-pub enum Ty {
-    Int,
-    String,
-    Float,
+mod container;
+
+use std::{
+    any::{TypeId, type_name},
+    sync::{Arc, Weak},
+};
+
+pub use container::{ArrayTy, OptionTy, StructTy, VecTy};
+pub use pha_engine_derive::Reflect;
+
+use crate::{GameObject, net::io::GameIoError};
+
+/// Maps a Rust type to the [`Ty`] it is serialized as.
+///
+/// # Safety
+///
+/// `TY` must describe the layout of `Self` exactly: serialization casts field pointers to the
+/// Rust type `TY` stands for and steps through containers by its size.
+///
+/// ```compile_fail,E0200
+/// use pha_engine::reflect::{ReflectTy, Ty};
+///
+/// struct Tiny(u8);
+///
+/// impl ReflectTy for Tiny {
+///     const TY: Ty = Ty::Mat4;
+/// }
+/// ```
+pub unsafe trait ReflectTy {
+    const TY: Ty;
 }
 
+macro_rules! reflect_types {
+    ($($variant:ident => $ty:ty),* $(,)?) => {
+        #[derive(Clone, Copy, Debug)]
+        pub enum Ty {
+            $($variant,)*
+            Vec(&'static VecTy),
+            Option(&'static OptionTy),
+            Array(&'static ArrayTy),
+            Struct(&'static StructTy),
+        }
+
+        impl Ty {
+            pub fn rust_type_id(self) -> TypeId {
+                match self {
+                    $(Ty::$variant => TypeId::of::<$ty>(),)*
+                    Ty::Vec(info) => (info.type_id)(),
+                    Ty::Option(info) => (info.type_id)(),
+                    Ty::Array(info) => (info.type_id)(),
+                    Ty::Struct(info) => (info.type_id)(),
+                }
+            }
+
+            pub const fn size(self) -> usize {
+                match self {
+                    $(Ty::$variant => size_of::<$ty>(),)*
+                    Ty::Vec(info) => info.size,
+                    Ty::Option(info) => info.size,
+                    Ty::Array(info) => info.size,
+                    Ty::Struct(info) => info.size,
+                }
+            }
+
+            pub const fn align(self) -> usize {
+                match self {
+                    $(Ty::$variant => align_of::<$ty>(),)*
+                    Ty::Vec(info) => info.align,
+                    Ty::Option(info) => info.align,
+                    Ty::Array(info) => info.align,
+                    Ty::Struct(info) => info.align,
+                }
+            }
+        }
+
+        $(
+            // SAFETY: `Ty::$variant` stands for exactly this type.
+            unsafe impl ReflectTy for $ty {
+                const TY: Ty = Ty::$variant;
+            }
+        )*
+    };
+}
+
+impl Ty {
+    /// The struct reached through this type, looking through containers.
+    pub fn nested_struct(self) -> Option<&'static StructTy> {
+        match self {
+            Ty::Vec(info) => info.elem.nested_struct(),
+            Ty::Option(info) => info.elem.nested_struct(),
+            Ty::Array(info) => info.elem.nested_struct(),
+            Ty::Struct(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    /// The innermost element type, looking through `Vec`, `Option` and arrays.
+    pub const fn leaf(self) -> Ty {
+        match self {
+            Ty::Vec(info) => info.elem.leaf(),
+            Ty::Option(info) => info.elem.leaf(),
+            Ty::Array(info) => info.elem.leaf(),
+            _ => self,
+        }
+    }
+
+    /// Checks that containers hold the element type their [`Ty`] says they do, and validates
+    /// the structs they hold, reporting mismatches against `field`.
+    fn validate(self, field: &'static str) -> Result<(), GameIoError> {
+        let (elem, type_id, size, align) = match self {
+            Ty::Vec(info) => (
+                info.elem,
+                info.elem_type_id,
+                info.elem_size,
+                info.elem_align,
+            ),
+            Ty::Option(info) => (
+                info.elem,
+                info.elem_type_id,
+                info.elem_size,
+                info.elem_align,
+            ),
+            Ty::Array(info) => (
+                info.elem,
+                info.elem_type_id,
+                info.elem_size,
+                info.elem_align,
+            ),
+            Ty::Struct(info) if (info.type_id)() != info.ty.rust_type_id() => {
+                return Err(GameIoError::InvalidTypeInfo(field));
+            }
+            Ty::Struct(info) => return info.ty.validate(),
+            _ => return Ok(()),
+        };
+
+        if type_id() != elem.rust_type_id() || size != elem.size() || align != elem.align() {
+            return Err(GameIoError::InvalidTypeInfo(field));
+        }
+
+        elem.validate(field)
+    }
+
+    const fn is_integer(self) -> bool {
+        matches!(
+            self,
+            Ty::U8 | Ty::U16 | Ty::U32 | Ty::U64 | Ty::I8 | Ty::I16 | Ty::I32 | Ty::I64
+        )
+    }
+
+    const fn is_float(self) -> bool {
+        matches!(
+            self,
+            Ty::F32
+                | Ty::F64
+                | Ty::Vec2
+                | Ty::Vec3
+                | Ty::Vec4
+                | Ty::Mat2
+                | Ty::Mat3
+                | Ty::Mat4
+                | Ty::Quat
+        )
+    }
+}
+
+reflect_types! {
+    Bool => bool,
+    U8 => u8,
+    U16 => u16,
+    U32 => u32,
+    U64 => u64,
+    I8 => i8,
+    I16 => i16,
+    I32 => i32,
+    I64 => i64,
+    F32 => f32,
+    F64 => f64,
+    String => String,
+    Vec2 => glam::Vec2,
+    Vec3 => glam::Vec3,
+    Vec4 => glam::Vec4,
+    Mat2 => glam::Mat2,
+    Mat3 => glam::Mat3,
+    Mat4 => glam::Mat4,
+    Quat => glam::Quat,
+    ObjectRef => Option<Arc<dyn GameObject>>,
+    WeakObjectRef => Option<Weak<dyn GameObject>>,
+}
+
+/// Builds a [`MemberField`] for `$field` of `$owner`, taking the offset and the field type from
+/// the struct itself.
+///
+/// ```compile_fail
+/// use pha_engine::{member_field, reflect::{MemberField, Ty}};
+///
+/// struct Cat {
+///     meows: u64,
+/// }
+///
+/// const MEOWS: MemberField = member_field!(Cat, meows, Ty::U32);
+/// ```
+#[macro_export]
+macro_rules! member_field {
+    ($owner:ty, $field:ident, $ty:expr) => {
+        const {
+            let ty: $crate::reflect::Ty = $ty;
+            let offset = ::core::mem::offset_of!($owner, $field);
+            let field: fn(&$owner) -> &_ = |owner| &owner.$field;
+
+            // SAFETY: the offset is taken from the very field the accessor returns.
+            unsafe { $crate::reflect::MemberField::new(stringify!($field), ty, offset, field) }
+        }
+    };
+}
+
+/// How a field is encoded by the bit-packed serializer. The byte serializer ignores it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Packing {
+    /// Every bit of the value, and a single bit for `bool`.
+    #[default]
+    Full,
+    /// The lowest `bits` bits of an integer, clamped to the representable range.
+    Bits(u32),
+    /// Each float component as a fixed-point number in `min..=max`, rounded to `precision`.
+    Fixed {
+        min: f32,
+        max: f32,
+        precision: f32,
+        bits: u32,
+    },
+}
+
+/// A reflected field of a struct, built with [`member_field!`] or `#[derive(Reflect)]`.
 #[derive(Debug)]
 pub struct MemberField {
-    pub name: &'static str,
-    pub ty: Ty,
-    pub offset: usize,
+    pub(crate) name: &'static str,
+    pub(crate) ty: Ty,
+    pub(crate) offset: usize,
+    pub(crate) owner_type_id: fn() -> TypeId,
+    pub(crate) type_id: fn() -> TypeId,
+    pub(crate) size: usize,
+    pub(crate) align: usize,
+    pub(crate) packing: Packing,
 }
 
 impl MemberField {
-    pub const fn new(name: &'static str, ty: Ty, offset: usize) -> Self {
-        Self { name, ty, offset }
+    /// `field` is only used to infer the owner and field types. Size or alignment mismatches
+    /// with `ty` are rejected at compile time when used in a constant, see
+    /// [`MemberField::is_valid`] for the full check.
+    ///
+    /// # Safety
+    ///
+    /// `offset` must be the offset in `S` of the field `field` returns.
+    pub const unsafe fn new<S: 'static, T: 'static>(
+        name: &'static str,
+        ty: Ty,
+        offset: usize,
+        _field: fn(&S) -> &T,
+    ) -> Self {
+        assert!(
+            size_of::<T>() == ty.size() && align_of::<T>() == ty.align(),
+            "field type does not match its `Ty`"
+        );
+
+        Self {
+            name,
+            ty,
+            offset,
+            owner_type_id: TypeId::of::<S>,
+            type_id: TypeId::of::<T>,
+            size: size_of::<T>(),
+            align: align_of::<T>(),
+            packing: Packing::Full,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn ty(&self) -> Ty {
+        self.ty
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn packing(&self) -> Packing {
+        self.packing
+    }
+
+    /// Packs an integer field, or the integers inside a container field, into `bits` bits.
+    pub const fn with_bits(mut self, bits: u32) -> Self {
+        let leaf = self.ty.leaf();
+        assert!(leaf.is_integer(), "`bits` requires an integer field");
+        assert!(
+            bits > 0 && bits as usize <= leaf.size() * 8,
+            "`bits` must fit the field type"
+        );
+
+        self.packing = Packing::Bits(bits);
+        self
+    }
+
+    /// Packs a float field, or the floats inside a glam or container field, as fixed-point
+    /// numbers in `min..=max` with the given `precision`.
+    pub const fn with_fixed(mut self, min: f32, max: f32, precision: f32) -> Self {
+        assert!(self.ty.leaf().is_float(), "`fixed` requires a float field");
+        assert!(min < max && precision > 0.0, "invalid fixed-point range");
+
+        let steps = ((max - min) / precision + 0.5) as u64;
+        let bits = u64::BITS - steps.leading_zeros();
+        assert!(
+            bits > 0 && bits <= u32::BITS,
+            "fixed-point range needs 1 to 32 bits"
+        );
+
+        self.packing = Packing::Fixed {
+            min,
+            max,
+            precision,
+            bits,
+        };
+        self
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (self.type_id)() == self.ty.rust_type_id()
+            && self.size == self.ty.size()
+            && self.align == self.ty.align()
+            && self.offset.is_multiple_of(self.align)
     }
 }
 
+/// The reflected fields of `T`, as passed to [`UserDefinedType::new`].
 #[derive(Debug)]
 pub struct UserDefinedType {
-    pub fields: &'static [MemberField],
+    pub(crate) fields: &'static [MemberField],
+    pub(crate) type_id: fn() -> TypeId,
+    pub(crate) size: usize,
 }
 
 impl UserDefinedType {
-    pub const fn new(fields: &'static [MemberField]) -> Self {
-        Self { fields }
+    pub const fn new<T: 'static>(fields: &'static [MemberField]) -> Self {
+        assert!(
+            fields.len() <= u64::BITS as usize,
+            "dirty state can track at most 64 fields"
+        );
+
+        Self {
+            fields,
+            type_id: TypeId::of::<T>,
+            size: size_of::<T>(),
+        }
+    }
+
+    pub fn fields(&self) -> &'static [MemberField] {
+        self.fields
+    }
+
+    /// The type whose fields these are.
+    pub fn rust_type_id(&self) -> TypeId {
+        (self.type_id)()
+    }
+
+    pub const fn all_fields_mask(&self) -> u64 {
+        match u64::MAX.checked_shr(u64::BITS - self.fields.len() as u32) {
+            Some(mask) => mask,
+            None => 0,
+        }
+    }
+
+    pub fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field.name == name)
+    }
+
+    /// Checks that every field belongs to this type, fits inside it and matches its [`Ty`],
+    /// down through container elements and nested structs.
+    pub fn validate(&self) -> Result<(), GameIoError> {
+        for field in self.fields {
+            let in_bounds = field
+                .offset
+                .checked_add(field.size)
+                .is_some_and(|end| end <= self.size);
+
+            if !field.is_valid() || (field.owner_type_id)() != self.rust_type_id() || !in_bounds {
+                return Err(GameIoError::InvalidTypeInfo(field.name));
+            }
+
+            field.ty.validate(field.name)?;
+        }
+
+        Ok(())
+    }
+
+    /// [`Self::validate`], and checks that these are the fields of `T`.
+    pub fn validate_for<T: 'static>(&self) -> Result<(), GameIoError> {
+        if self.rust_type_id() != TypeId::of::<T>() {
+            return Err(GameIoError::InvalidTypeInfo(type_name::<T>()));
+        }
+
+        self.validate()
     }
 }
 
@@ -34,3 +409,488 @@ pub trait Reflect {
     fn type_id() -> u32;
     fn create_instance() -> Self;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::mem::offset_of;
+
+    use crate::{
+        io::{
+            bits::{BitReadable, BitWritable},
+            bytes::{Readable, Writable},
+        },
+        linking_context::LinkingContext,
+        net::io::{GameIoError, InputMemoryStream, OutputMemoryStream},
+    };
+
+    use super::{MemberField, Reflect, ReflectTy, Ty, UserDefinedType};
+
+    #[derive(Debug, Reflect)]
+    struct Cat {
+        #[reflect(default = 10)]
+        health: u32,
+        #[reflect(skip)]
+        local_only: Vec<u8>,
+        #[reflect(default = "Tom".to_string())]
+        name: String,
+        speed: f32,
+    }
+
+    #[derive(Debug, Reflect)]
+    #[reflect(type_id = 42)]
+    struct Dog {
+        barks: u32,
+    }
+
+    #[derive(Debug, Default, PartialEq, Reflect)]
+    struct Transform {
+        visible: bool,
+        layer: u8,
+        flags: u16,
+        score: u64,
+        offset: i8,
+        delta: i16,
+        team: i32,
+        timestamp: i64,
+        mass: f64,
+        uv: glam::Vec2,
+        position: glam::Vec3,
+        color: glam::Vec4,
+        basis: glam::Mat2,
+        normal: glam::Mat3,
+        world: glam::Mat4,
+        rotation: glam::Quat,
+    }
+
+    #[derive(Debug, Default, PartialEq, Reflect)]
+    struct Item {
+        id: u32,
+        label: String,
+    }
+
+    #[derive(Debug, Default, PartialEq, Reflect)]
+    struct Health {
+        current: u32,
+        max: u32,
+    }
+
+    #[derive(Debug, Default, PartialEq, Reflect)]
+    struct Inventory {
+        items: Vec<Item>,
+        target: Option<u32>,
+        nothing: Option<String>,
+        slots: [u8; 4],
+        equipped: [Option<Item>; 2],
+        health: Health,
+        history: Vec<Vec<f32>>,
+    }
+
+    #[derive(Debug, Default, PartialEq, Reflect)]
+    struct Packed {
+        #[reflect(bits = 7)]
+        health: u32,
+        #[reflect(bits = 5)]
+        offset: i8,
+        alive: bool,
+        #[reflect(fixed(min = -100.0, max = 100.0, precision = 0.01))]
+        position: glam::Vec3,
+        #[reflect(fixed(min = 0.0, max = 1.0, precision = 0.001))]
+        speed: f32,
+        #[reflect(bits = 4)]
+        slots: Vec<u8>,
+        name: String,
+    }
+
+    #[derive(Debug, Default)]
+    struct Mislabeled {
+        speed: f32,
+    }
+
+    impl Reflect for Mislabeled {
+        fn reflect(&self) -> &'static UserDefinedType {
+            const INFO: &UserDefinedType =
+                &UserDefinedType::new::<Mislabeled>(&[member_field!(Mislabeled, speed, Ty::U32)]);
+
+            INFO
+        }
+
+        fn type_id() -> u32 {
+            7
+        }
+
+        fn create_instance() -> Self {
+            Default::default()
+        }
+    }
+
+    #[test]
+    fn mismatched_field_type_is_rejected() {
+        let value = Mislabeled::default();
+        assert!(matches!(
+            value.reflect().validate(),
+            Err(GameIoError::InvalidTypeInfo("speed"))
+        ));
+
+        let mut ctx = LinkingContext::default();
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        assert!(matches!(
+            value.write_byte(&mut output),
+            Err(GameIoError::InvalidTypeInfo("speed"))
+        ));
+        assert!(buf.is_empty());
+    }
+
+    #[derive(Debug, Default)]
+    struct Small {
+        speed: f32,
+    }
+
+    /// A field past the end of its owner, which [`member_field!`] cannot build.
+    const FAR: MemberField =
+        unsafe { MemberField::new("far", Ty::F32, 4096, |s: &Small| &s.speed) };
+
+    impl Reflect for Small {
+        fn reflect(&self) -> &'static UserDefinedType {
+            const INFO: &UserDefinedType = &UserDefinedType::new::<Small>(&[FAR]);
+
+            INFO
+        }
+
+        fn type_id() -> u32 {
+            8
+        }
+
+        fn create_instance() -> Self {
+            Default::default()
+        }
+    }
+
+    /// Claims the fields of another type.
+    #[derive(Debug, Default)]
+    struct Impostor;
+
+    impl Reflect for Impostor {
+        fn reflect(&self) -> &'static UserDefinedType {
+            Transform::default().reflect()
+        }
+
+        fn type_id() -> u32 {
+            9
+        }
+
+        fn create_instance() -> Self {
+            Impostor
+        }
+    }
+
+    /// Borrows a field of another type.
+    #[derive(Debug, Default)]
+    struct Borrower {
+        _speed: f32,
+    }
+
+    impl Reflect for Borrower {
+        fn reflect(&self) -> &'static UserDefinedType {
+            const INFO: &UserDefinedType =
+                &UserDefinedType::new::<Borrower>(&[member_field!(Small, speed, Ty::F32)]);
+
+            INFO
+        }
+
+        fn type_id() -> u32 {
+            10
+        }
+
+        fn create_instance() -> Self {
+            Default::default()
+        }
+    }
+
+    /// Deliberately breaks the [`ReflectTy`] contract to check that it is caught.
+    #[derive(Debug, Default, Clone, Copy)]
+    struct Tiny {
+        _byte: u8,
+    }
+
+    unsafe impl ReflectTy for Tiny {
+        const TY: Ty = Ty::Mat4;
+    }
+
+    #[derive(Debug, Default, Reflect)]
+    struct TinyVec {
+        tiny_vec: Vec<Tiny>,
+    }
+
+    #[derive(Debug, Default, Reflect)]
+    struct TinyOption {
+        tiny_option: Option<Tiny>,
+    }
+
+    #[derive(Debug, Default, Reflect)]
+    struct TinyArray {
+        tiny_array: [Tiny; 2],
+    }
+
+    #[test]
+    fn fields_outside_their_owner_are_rejected() {
+        let mut ctx = LinkingContext::default();
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+
+        assert!(matches!(
+            Small::default().reflect().validate(),
+            Err(GameIoError::InvalidTypeInfo("far"))
+        ));
+        assert!(matches!(
+            Small::default().write_byte(&mut output),
+            Err(GameIoError::InvalidTypeInfo("far"))
+        ));
+        assert!(matches!(
+            Borrower::default().write_byte(&mut output),
+            Err(GameIoError::InvalidTypeInfo("speed"))
+        ));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn container_elements_not_matching_their_ty_are_rejected() {
+        let mut ctx = LinkingContext::default();
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+
+        let tiny_vec = TinyVec {
+            tiny_vec: vec![Tiny::default(); 2],
+        };
+        assert!(matches!(
+            tiny_vec.reflect().validate(),
+            Err(GameIoError::InvalidTypeInfo("tiny_vec"))
+        ));
+        assert!(matches!(
+            tiny_vec.write_byte(&mut output),
+            Err(GameIoError::InvalidTypeInfo("tiny_vec"))
+        ));
+        assert!(matches!(
+            TinyOption::default().reflect().validate(),
+            Err(GameIoError::InvalidTypeInfo("tiny_option"))
+        ));
+        assert!(matches!(
+            TinyArray::default().reflect().validate(),
+            Err(GameIoError::InvalidTypeInfo("tiny_array"))
+        ));
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn type_info_of_another_type_is_rejected() {
+        let mut ctx = LinkingContext::default();
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+
+        assert!(Impostor.reflect().validate().is_ok());
+        assert!(matches!(
+            Impostor.write_byte(&mut output),
+            Err(GameIoError::InvalidTypeInfo(name)) if name.ends_with("Impostor")
+        ));
+        assert!(matches!(
+            Impostor::read_bits(&mut InputMemoryStream::new(&[0; 64], &mut ctx), 0),
+            Err(GameIoError::InvalidTypeInfo(name)) if name.ends_with("Impostor")
+        ));
+    }
+
+    #[test]
+    fn primitive_and_glam_fields_round_trip() {
+        let transform = Transform {
+            visible: true,
+            layer: 200,
+            flags: 0xBEEF,
+            score: u64::MAX - 1,
+            offset: -5,
+            delta: -1234,
+            team: i32::MIN,
+            timestamp: -1,
+            mass: 12.5,
+            uv: glam::Vec2::new(0.25, 0.75),
+            position: glam::Vec3::new(1.0, -2.0, 3.0),
+            color: glam::Vec4::new(0.1, 0.2, 0.3, 1.0),
+            basis: glam::Mat2::from_angle(1.0),
+            normal: glam::Mat3::from_rotation_y(0.5),
+            world: glam::Mat4::from_translation(glam::Vec3::new(4.0, 5.0, 6.0)),
+            rotation: glam::Quat::from_rotation_z(0.3),
+        };
+
+        let mut ctx = LinkingContext::default();
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        transform.write_byte(&mut output).unwrap();
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        assert_eq!(Transform::read_byte(&mut input).unwrap(), transform);
+        assert_eq!(input.remaining_bit_count(), 0);
+    }
+
+    #[test]
+    fn containers_and_nested_structs_round_trip() {
+        let inventory = Inventory {
+            items: vec![
+                Item {
+                    id: 1,
+                    label: "sword".to_string(),
+                },
+                Item {
+                    id: 2,
+                    label: "shield".to_string(),
+                },
+            ],
+            target: Some(7),
+            nothing: None,
+            slots: [1, 2, 3, 4],
+            equipped: [
+                None,
+                Some(Item {
+                    id: 3,
+                    label: "hat".to_string(),
+                }),
+            ],
+            health: Health {
+                current: 5,
+                max: 10,
+            },
+            history: vec![vec![], vec![1.0, 2.0]],
+        };
+
+        let mut ctx = LinkingContext::default();
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        inventory.write_byte(&mut output).unwrap();
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        assert_eq!(Inventory::read_byte(&mut input).unwrap(), inventory);
+        assert_eq!(input.remaining_bit_count(), 0);
+    }
+
+    #[test]
+    fn container_encoding_matches_io_bytes() {
+        let item = Item {
+            id: 9,
+            label: "cap".to_string(),
+        };
+
+        let mut ctx = LinkingContext::default();
+        let mut reflected = vec![];
+        let mut output = OutputMemoryStream::new(&mut reflected, &mut ctx);
+        Inventory {
+            items: vec![Item {
+                id: 9,
+                label: "cap".to_string(),
+            }],
+            target: Some(3),
+            ..Default::default()
+        }
+        .write_byte(&mut output)
+        .unwrap();
+
+        let mut manual = vec![];
+        let mut output = OutputMemoryStream::new(&mut manual, &mut ctx);
+        vec![item].write_byte(&mut output).unwrap();
+        Some(3u32).write_byte(&mut output).unwrap();
+
+        assert_eq!(reflected[..manual.len()], manual[..]);
+    }
+
+    #[test]
+    fn packed_fields_use_their_bit_widths() {
+        let packed = Packed {
+            health: 100,
+            offset: -9,
+            alive: true,
+            position: glam::Vec3::new(12.345, -99.99, 0.004),
+            speed: 0.5,
+            slots: vec![1, 15],
+            name: "Tom".to_string(),
+        };
+
+        let mut ctx = LinkingContext::default();
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        packed.write_bits(&mut output, 0).unwrap();
+
+        let bits = 7 + 5 + 1 + 3 * 15 + 10 + (8 + 2 * 4) + (8 + 3 * 8);
+        assert_eq!(buf.len(), usize::div_ceil(bits, 8));
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        let read = Packed::read_bits(&mut input, 0).unwrap();
+        assert_eq!(input.remaining_bit_count(), buf.len() * 8 - bits);
+
+        assert_eq!(read.health, 100);
+        assert_eq!(read.offset, -9);
+        assert!(read.alive);
+        assert!(read.position.abs_diff_eq(packed.position, 0.005));
+        assert!((read.speed - packed.speed).abs() <= 0.0005);
+        assert_eq!(read.slots, packed.slots);
+        assert_eq!(read.name, packed.name);
+    }
+
+    #[test]
+    fn packed_fields_are_clamped_to_their_range() {
+        let packed = Packed {
+            health: 200,
+            offset: -100,
+            position: glam::Vec3::new(1000.0, -1000.0, 0.0),
+            speed: 2.0,
+            slots: vec![255],
+            ..Default::default()
+        };
+
+        let mut ctx = LinkingContext::default();
+        let mut buf = vec![];
+        let mut output = OutputMemoryStream::new(&mut buf, &mut ctx);
+        packed.write_bits(&mut output, 0).unwrap();
+
+        let mut input = InputMemoryStream::new(&buf, &mut ctx);
+        let read = Packed::read_bits(&mut input, 0).unwrap();
+
+        assert_eq!(read.health, 127);
+        assert_eq!(read.offset, -16);
+        assert!(
+            read.position
+                .abs_diff_eq(glam::Vec3::new(100.0, -100.0, 0.0), 0.005)
+        );
+        assert!((read.speed - 1.0).abs() <= 0.0005);
+        assert_eq!(read.slots, [15]);
+    }
+
+    #[test]
+    fn derive_describes_fields() {
+        let cat = Cat::create_instance();
+        let fields = cat.reflect().fields;
+
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0].name, "health");
+        assert!(matches!(fields[0].ty, Ty::U32));
+        assert_eq!(fields[0].offset, offset_of!(Cat, health));
+        assert_eq!(fields[1].name, "name");
+        assert!(matches!(fields[1].ty, Ty::String));
+        assert_eq!(fields[1].offset, offset_of!(Cat, name));
+        assert_eq!(fields[2].name, "speed");
+        assert!(matches!(fields[2].ty, Ty::F32));
+        assert_eq!(fields[2].offset, offset_of!(Cat, speed));
+    }
+
+    #[test]
+    fn derive_applies_defaults() {
+        let cat = Cat::create_instance();
+
+        assert_eq!(cat.health, 10);
+        assert_eq!(cat.name, "Tom");
+        assert_eq!(cat.speed, 0.0);
+        assert!(cat.local_only.is_empty());
+    }
+
+    #[test]
+    fn derive_type_id_is_stable() {
+        assert_eq!(Cat::type_id(), 0x68fe_f3a7);
+        assert_eq!(Dog::type_id(), 42);
+        assert_eq!(Dog::create_instance().barks, 0);
+    }
+}